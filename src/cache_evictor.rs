@@ -0,0 +1,279 @@
+//! Least-recently-used eviction to keep the cache within a size budget.
+//!
+//! Follows hath-rust's approach: walk the cache tree to build an
+//! access-time ordering over what's actually on disk, then evict the
+//! oldest entries until back under budget. Every eviction pass rescans the
+//! whole tree to rebuild the total; the running total only keeps the last
+//! pass's figure for display (`total()`) and is not used to skip a rescan.
+
+use crate::content_store::{ChunkRef, ContentStore, FileIndex};
+use std::collections::HashSet;
+use std::os::linux::fs::MetadataExt;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicI64, Ordering};
+use std::sync::Arc;
+
+/// A single evictable unit found while walking the cache tree: either a
+/// chunk-indexed file (`.idx`, whose chunks may be shared with other files)
+/// or a raw cache file written by the parallel download path.
+enum Entry {
+    Indexed { idx_path: PathBuf, chunks: Vec<ChunkRef> },
+    Raw { path: PathBuf, size: i64 },
+}
+
+impl Entry {
+    fn cache_path(&self) -> PathBuf {
+        match self {
+            Entry::Indexed { idx_path, .. } => idx_path.with_extension(""),
+            Entry::Raw { path, .. } => path.clone(),
+        }
+    }
+}
+
+/// Sums the on-disk footprint of `entries`, counting each distinct chunk
+/// digest once regardless of how many indexed files reference it — a chunk
+/// shared by two cached files occupies the budget once, not twice.
+fn dedup_total(entries: &[(i64, Entry)]) -> i64 {
+    let mut seen = HashSet::new();
+    let mut total: i64 = 0;
+    for (_, entry) in entries {
+        match entry {
+            Entry::Indexed { chunks, .. } => {
+                for chunk in chunks {
+                    if seen.insert(chunk.digest.clone()) {
+                        total += chunk.disk_len as i64;
+                    }
+                }
+            }
+            Entry::Raw { size, .. } => total += size,
+        }
+    }
+    total
+}
+
+pub struct CacheEvictor {
+    cache_root: PathBuf,
+    content_store: Arc<ContentStore>,
+    budget: i64,
+    total: AtomicI64,
+}
+
+impl CacheEvictor {
+    /// `budget` is the size cap in bytes; `<= 0` disables eviction entirely.
+    pub fn new(cache_root: PathBuf, content_store: Arc<ContentStore>, budget: i64) -> Self {
+        Self {
+            cache_root,
+            content_store,
+            budget,
+            total: AtomicI64::new(0),
+        }
+    }
+
+    pub fn budget(&self) -> i64 {
+        self.budget
+    }
+
+    pub fn total(&self) -> i64 {
+        self.total.load(Ordering::Relaxed)
+    }
+
+    /// Rescans the cache tree, and if the total exceeds the budget, deletes
+    /// least-recently-accessed entries (skipping anything in `protected`)
+    /// until it doesn't.
+    pub async fn evict_if_over_budget(&self, protected: &HashSet<PathBuf>) -> std::io::Result<()> {
+        if self.budget <= 0 {
+            return Ok(());
+        }
+
+        let mut entries = Vec::new();
+        walk(&self.cache_root, &mut entries).await?;
+
+        let total = dedup_total(&entries);
+        self.total.store(total, Ordering::Relaxed);
+        if total <= self.budget {
+            return Ok(());
+        }
+
+        // Oldest access time first.
+        entries.sort_by_key(|(atime, _)| *atime);
+
+        let mut remaining = total;
+        for (_, entry) in entries {
+            if remaining <= self.budget {
+                break;
+            }
+            if protected.contains(&entry.cache_path()) {
+                continue;
+            }
+
+            let freed = match &entry {
+                Entry::Indexed { idx_path, .. } => self.evict_indexed(idx_path).await?,
+                Entry::Raw { path, .. } => evict_raw(path).await?,
+            };
+            remaining -= freed;
+        }
+
+        self.total.store(remaining.max(0), Ordering::Relaxed);
+        Ok(())
+    }
+
+    /// Releases every chunk an index references and removes the index
+    /// itself. Only chunks `release_chunk` actually deletes (i.e. whose
+    /// refcount dropped to zero) count toward the freed total — a chunk
+    /// still shared with another live file stays on disk, so evicting this
+    /// file doesn't free its bytes.
+    async fn evict_indexed(&self, idx_path: &Path) -> std::io::Result<i64> {
+        let index = FileIndex::load(idx_path)?;
+        let mut freed = 0i64;
+        for chunk in &index.chunks {
+            if self.content_store.release_chunk(&chunk.digest).await? {
+                freed += chunk.disk_len as i64;
+            }
+        }
+        tokio::fs::remove_file(idx_path).await?;
+        Ok(freed)
+    }
+}
+
+async fn evict_raw(path: &Path) -> std::io::Result<i64> {
+    let metadata = tokio::fs::metadata(path).await?;
+    let freed = metadata.st_blocks() as i64 * 512;
+    tokio::fs::remove_file(path).await?;
+    Ok(freed)
+}
+
+/// Filename of the persisted job index (see `cache_manager::JOB_INDEX_FILENAME`),
+/// plus the `.tmp` file its atomic save briefly renames through — both sit
+/// directly under the cache root alongside cached files and must never be
+/// swept up as an evictable entry.
+const JOB_INDEX_FILENAME: &str = "jobs.state";
+const JOB_INDEX_TMP_FILENAME: &str = "jobs.tmp";
+
+/// Recursively collects `(atime, Entry)` pairs for everything under `dir`,
+/// skipping the content store itself (its chunks are already counted
+/// through the `.idx` files that reference them) and the persisted job
+/// index, which isn't cached file content and must survive eviction.
+fn walk<'a>(
+    dir: &'a Path,
+    out: &'a mut Vec<(i64, Entry)>,
+) -> std::pin::Pin<Box<dyn std::future::Future<Output = std::io::Result<()>> + Send + 'a>> {
+    Box::pin(async move {
+        let mut read_dir = match tokio::fs::read_dir(dir).await {
+            Ok(rd) => rd,
+            Err(_) => return Ok(()),
+        };
+
+        while let Some(entry) = read_dir.next_entry().await? {
+            let path = entry.path();
+            let metadata = match entry.metadata().await {
+                Ok(m) => m,
+                Err(_) => continue,
+            };
+
+            if metadata.is_dir() {
+                if path.file_name().and_then(|n| n.to_str()) == Some("store") {
+                    continue;
+                }
+                walk(&path, out).await?;
+                continue;
+            }
+
+            match path.file_name().and_then(|n| n.to_str()) {
+                Some(JOB_INDEX_FILENAME) | Some(JOB_INDEX_TMP_FILENAME) => continue,
+                _ => {}
+            }
+
+            let atime = metadata.st_atime();
+            if path.extension().and_then(|e| e.to_str()) == Some("idx") {
+                if let Ok(index) = FileIndex::load(&path) {
+                    out.push((
+                        atime,
+                        Entry::Indexed {
+                            idx_path: path,
+                            chunks: index.chunks,
+                        },
+                    ));
+                }
+            } else {
+                out.push((
+                    atime,
+                    Entry::Raw {
+                        path,
+                        size: metadata.st_blocks() as i64 * 512,
+                    },
+                ));
+            }
+        }
+
+        Ok(())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::temp_cache_root;
+
+    fn chunk_ref(digest: &str, disk_len: u64) -> ChunkRef {
+        ChunkRef {
+            digest: digest.to_string(),
+            offset: 0,
+            len: disk_len,
+            disk_len,
+        }
+    }
+
+    #[test]
+    fn dedup_total_counts_a_shared_chunk_once() {
+        let shared = chunk_ref("shared-digest", 900);
+        let unique_a = chunk_ref("unique-a", 100);
+        let unique_b = chunk_ref("unique-b", 200);
+
+        let entries = vec![
+            (
+                1,
+                Entry::Indexed {
+                    idx_path: PathBuf::from("/cache/a.idx"),
+                    chunks: vec![shared.clone(), unique_a],
+                },
+            ),
+            (
+                2,
+                Entry::Indexed {
+                    idx_path: PathBuf::from("/cache/b.idx"),
+                    chunks: vec![shared, unique_b],
+                },
+            ),
+        ];
+
+        // The shared chunk's 900 bytes must only be counted once, not twice.
+        assert_eq!(dedup_total(&entries), 900 + 100 + 200);
+    }
+
+    #[test]
+    fn dedup_total_adds_raw_entries_directly() {
+        let entries = vec![(1, Entry::Raw { path: PathBuf::from("/cache/raw.bin"), size: 4096 })];
+        assert_eq!(dedup_total(&entries), 4096);
+    }
+
+    #[tokio::test]
+    async fn walk_never_collects_the_job_index_as_evictable() {
+        let root = temp_cache_root("walk-job-index");
+        std::fs::write(root.join(JOB_INDEX_FILENAME), b"fake persisted job state").unwrap();
+        std::fs::write(root.join("a-raw-cache-file"), b"cached bytes").unwrap();
+
+        let mut entries = Vec::new();
+        walk(&root, &mut entries).await.unwrap();
+
+        assert!(
+            entries
+                .iter()
+                .all(|(_, entry)| entry.cache_path().file_name().and_then(|n| n.to_str())
+                    != Some(JOB_INDEX_FILENAME)),
+            "jobs.state must never be picked up as an evictable entry"
+        );
+        assert_eq!(entries.len(), 1);
+
+        let _ = std::fs::remove_dir_all(&root);
+    }
+}
@@ -1,28 +1,461 @@
+use crate::cache_evictor::CacheEvictor;
+use crate::content_store::{ChunkRef, ContentDefinedChunker, ContentStore, FileIndex};
+use crate::job_index::{JobIndex, JobRecord};
 use crate::models::{CacheProgress, GlobalProgress};
 use crate::storage_backend::StorageBackend;
 use parking_lot::RwLock;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::future::Future;
-use std::path::PathBuf;
+use std::os::unix::fs::FileExt;
+use std::path::{Path, PathBuf};
 use std::pin::Pin;
 use std::sync::Arc;
 use tokio::io::AsyncReadExt;
+use tokio::sync::{Notify, Semaphore};
+
+/// How often the background eviction pass rechecks the cache against budget,
+/// independent of job completions.
+const EVICTION_INTERVAL: std::time::Duration = std::time::Duration::from_secs(60);
+
+/// Suffix for the per-file chunk index that replaces the old raw cache copy.
+const INDEX_SUFFIX: &str = ".idx";
+
+/// Filename of the persisted job index, stored directly under the cache root
+/// alongside the content store's `store/` directory.
+const JOB_INDEX_FILENAME: &str = "jobs.state";
+
+/// Files above this size skip content-defined chunking and are instead
+/// fetched as fixed-size ranges spread across worker tasks, since parallel
+/// ranged writes can't feed a single sequential rolling hash. Below the
+/// threshold, the dedup savings of CDC outweigh the win from parallelism.
+const PARALLEL_THRESHOLD: u64 = 64 * 1024 * 1024;
+
+/// Path of the chunk index that describes the cached copy of `cache_path`.
+fn index_path(cache_path: &Path) -> PathBuf {
+    let mut name = cache_path.as_os_str().to_owned();
+    name.push(INDEX_SUFFIX);
+    PathBuf::from(name)
+}
+
+/// Replays `chunks` to reconstruct `[start, end]` (inclusive) of the
+/// logical file they describe, or `None` if any part of the range isn't
+/// covered by `chunks`.
+async fn read_chunks_range(
+    content_store: &ContentStore,
+    chunks: &[ChunkRef],
+    start: u64,
+    end: u64,
+) -> Option<Vec<u8>> {
+    let mut out = Vec::with_capacity((end - start + 1) as usize);
+    for chunk in chunks {
+        let chunk_end = chunk.offset + chunk.len - 1;
+        if chunk_end < start || chunk.offset > end {
+            continue;
+        }
+
+        let data = content_store.read_chunk(&chunk.digest).await.ok()?;
+        let lo = start.saturating_sub(chunk.offset) as usize;
+        let hi = (end.min(chunk_end) - chunk.offset + 1) as usize;
+        out.extend_from_slice(&data[lo..hi]);
+    }
+
+    if out.len() as u64 == end - start + 1 {
+        Some(out)
+    } else {
+        None
+    }
+}
+
+/// Reads `[start, end]` (inclusive) directly from the raw cache file the
+/// parallel range-download path writes to — it has no chunk index of its
+/// own to replay.
+async fn read_raw_range(cache_path: &Path, start: u64, end: u64) -> std::io::Result<Vec<u8>> {
+    let cache_path = cache_path.to_path_buf();
+    tokio::task::spawn_blocking(move || -> std::io::Result<Vec<u8>> {
+        let file = std::fs::File::open(&cache_path)?;
+        let len = (end - start + 1) as usize;
+        let mut buf = vec![0u8; len];
+        let mut read = 0;
+        while read < len {
+            let n = file.read_at(&mut buf[read..], start + read as u64)?;
+            if n == 0 {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::UnexpectedEof,
+                    "short read from cache file",
+                ));
+            }
+            read += n;
+        }
+        Ok(buf)
+    })
+    .await
+    .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))?
+}
+
+/// Bytes of `[0, total_size)` the parallel range-download path has written
+/// *contiguously* from the start, per its `completed_chunks` index set —
+/// chunks can land out of order, so a later range being done doesn't make it
+/// safe to read past the first gap.
+fn contiguous_frontier(completed: &HashSet<usize>, chunk_size: u64, total_size: u64) -> u64 {
+    let mut bytes = 0u64;
+    let mut index = 0usize;
+    while bytes < total_size && completed.contains(&index) {
+        bytes = (bytes + chunk_size).min(total_size);
+        index += 1;
+    }
+    bytes
+}
+
+/// Range indices a previous run of the parallel download path already wrote
+/// durably for this job, safe to skip re-fetching this run — but only if
+/// the job is for the same cache path, the same source size, *and* the same
+/// chunk size. A different `--chunk` value reinterprets these indices
+/// against different byte boundaries, so reusing them would write fetched
+/// bytes at the wrong offset and leave stale-but-"done" regions zero-filled
+/// forever.
+fn resumable_chunks(
+    job: Option<&JobRecord>,
+    cache_path: &Path,
+    total_size: u64,
+    chunk_size: usize,
+) -> HashSet<usize> {
+    job.filter(|job| {
+        job.cache_path == cache_path && job.total_size == total_size as i64 && job.chunk_size == chunk_size
+    })
+    .map(|job| job.completed_chunks.clone())
+    .unwrap_or_default()
+}
+
+/// How to read a range `stream_cached` has determined is safe to serve:
+/// either by replaying chunk references (the CDC path, live or finished), or
+/// by reading the raw cache file directly (the parallel range-download
+/// path, which never builds a `FileIndex`).
+enum Readable {
+    Chunks(Vec<ChunkRef>),
+    Raw,
+}
+
+/// Cache paths of all in-flight jobs, so the evictor never deletes a file
+/// that's still being written.
+fn protected_paths(
+    active: &RwLock<HashMap<String, Arc<RwLock<CacheProgress>>>>,
+    cache_root: &Path,
+) -> HashSet<PathBuf> {
+    active
+        .read()
+        .keys()
+        .map(|source_path| cache_root.join(source_path.trim_start_matches('/')))
+        .collect()
+}
+
+/// Tracks how far a still-in-progress `cache_file_inner` has gotten, so a
+/// concurrent reader can stream the bytes that already landed instead of
+/// waiting for the whole job to finish. Ported from mangadex-home-rs'
+/// writing-status relay.
+pub struct WriteStatus {
+    chunks: RwLock<Vec<ChunkRef>>,
+    flushed: std::sync::atomic::AtomicU64,
+    notify: Notify,
+}
+
+impl WriteStatus {
+    fn new() -> Self {
+        Self {
+            chunks: RwLock::new(Vec::new()),
+            flushed: std::sync::atomic::AtomicU64::new(0),
+            notify: Notify::new(),
+        }
+    }
+
+    fn push_chunk(&self, chunk: ChunkRef) {
+        let flushed_through = chunk.offset + chunk.len;
+        self.chunks.write().push(chunk);
+        self.flushed
+            .store(flushed_through, std::sync::atomic::Ordering::SeqCst);
+        self.notify.notify_waiters();
+    }
+
+    /// Highest logical offset flushed so far (i.e. readable up to, exclusive).
+    pub fn flushed(&self) -> u64 {
+        self.flushed.load(std::sync::atomic::Ordering::SeqCst)
+    }
+
+    pub fn chunks_snapshot(&self) -> Vec<ChunkRef> {
+        self.chunks.read().clone()
+    }
+
+    /// Waits until more bytes are flushed. Also resolves once the job
+    /// finishes (the `WriteStatusGuard` that owns this status wakes every
+    /// waiter on drop), so callers must re-check whether the job is still
+    /// active after this returns.
+    pub async fn notified(&self) {
+        self.notify.notified().await;
+    }
+}
+
+/// Removes a path's `WriteStatus` once its job is done, win or lose, and
+/// wakes any reader still blocked on it so it can notice the job finished.
+struct WriteStatusGuard {
+    map: Arc<RwLock<HashMap<PathBuf, Arc<WriteStatus>>>>,
+    cache_path: PathBuf,
+    status: Arc<WriteStatus>,
+}
+
+impl Drop for WriteStatusGuard {
+    fn drop(&mut self) {
+        self.map.write().remove(&self.cache_path);
+        self.status.notify.notify_waiters();
+    }
+}
 
 pub struct CacheManager {
     chunk_size: usize,
     storage_backend: Arc<dyn StorageBackend>,
+    content_store: Arc<ContentStore>,
+    cache_root: PathBuf,
     active: Arc<RwLock<HashMap<String, Arc<RwLock<CacheProgress>>>>>,
+    evictor: Option<Arc<CacheEvictor>>,
+    write_status: Arc<RwLock<HashMap<PathBuf, Arc<WriteStatus>>>>,
+    job_index: Arc<RwLock<JobIndex>>,
+    job_index_path: Arc<PathBuf>,
 }
 
 impl CacheManager {
-    pub fn new(chunk_size: usize, storage_backend: Arc<dyn StorageBackend>) -> Self {
-        Self {
+    /// `cache_budget_bytes` caps the on-disk cache size; `None` (or `<= 0`)
+    /// disables eviction and lets the cache grow unbounded.
+    pub fn new(
+        chunk_size: usize,
+        cache_root: PathBuf,
+        storage_backend: Arc<dyn StorageBackend>,
+        cache_budget_bytes: Option<i64>,
+    ) -> std::io::Result<Self> {
+        let content_store = Arc::new(ContentStore::new(&cache_root)?);
+        let active = Arc::new(RwLock::new(HashMap::new()));
+
+        let job_index_path = Arc::new(cache_root.join(JOB_INDEX_FILENAME));
+        let job_index = Arc::new(RwLock::new(JobIndex::load(&job_index_path)));
+
+        let evictor = cache_budget_bytes
+            .filter(|budget| *budget > 0)
+            .map(|budget| {
+                Arc::new(CacheEvictor::new(
+                    cache_root.clone(),
+                    Arc::clone(&content_store),
+                    budget,
+                ))
+            });
+
+        if let Some(evictor) = evictor.clone() {
+            let active = Arc::clone(&active);
+            let cache_root = cache_root.clone();
+            tokio::spawn(async move {
+                let mut interval = tokio::time::interval(EVICTION_INTERVAL);
+                loop {
+                    interval.tick().await;
+                    let protected = protected_paths(&active, &cache_root);
+                    if let Err(e) = evictor.evict_if_over_budget(&protected).await {
+                        tracing::error!("Error during periodic cache eviction: {}", e);
+                    }
+                }
+            });
+        }
+
+        Ok(Self {
             chunk_size,
             storage_backend,
-            active: Arc::new(RwLock::new(HashMap::new())),
+            content_store,
+            cache_root,
+            active,
+            evictor,
+            write_status: Arc::new(RwLock::new(HashMap::new())),
+            job_index,
+            job_index_path,
+        })
+    }
+
+    /// Upserts `record` into the in-memory job index and flushes it to disk.
+    fn persist_job(&self, record: JobRecord) {
+        self.job_index.write().upsert(record);
+        if let Err(e) = self.job_index.read().save(&self.job_index_path) {
+            tracing::error!("Error persisting job index: {}", e);
         }
     }
 
+    /// Whether `cache_path` already holds the complete, *current* contents
+    /// of a `total_size`-byte file, whether as a finished chunk index (CDC
+    /// path) or a raw copy (parallel download path).
+    async fn already_cached(
+        storage_backend: &Arc<dyn StorageBackend>,
+        source_path: &str,
+        cache_path: &Path,
+        total_size: i64,
+    ) -> bool {
+        let idx_path = index_path(cache_path);
+        if let Ok(index) = FileIndex::load(&idx_path) {
+            if index.logical_size() != total_size.max(0) as u64 {
+                return false;
+            }
+            // Same size isn't proof the content is unchanged — a remote
+            // restart or a same-size replacement file would otherwise be
+            // served from cache forever with zero revalidation. Spend a
+            // conditional GET here too, not just on the next explicit
+            // precache, so a restart's resume path can't skip it.
+            return matches!(
+                storage_backend
+                    .open_file_conditional(
+                        source_path,
+                        index.etag.as_deref(),
+                        Some(index.last_modified),
+                    )
+                    .await,
+                Ok(crate::storage_backend::ConditionalFetch::NotModified)
+            );
+        }
+
+        // The parallel download path's `JobRecord` doesn't carry an
+        // etag/last-modified validator, so a raw cached copy has nothing to
+        // revalidate against here — a same-size replacement file on the
+        // remote is indistinguishable from an unchanged one and this falls
+        // back to the size-only check. The parallel path in
+        // `cache_file_inner` doesn't revalidate either, so such a change
+        // goes undetected.
+        std::fs::metadata(cache_path)
+            .map(|metadata| metadata.len() == total_size.max(0) as u64)
+            .unwrap_or(false)
+    }
+
+    /// Shared handle to the chunk store, for reconstructing logical bytes
+    /// outside of `CacheManager` itself (e.g. read-through streaming).
+    pub fn content_store(&self) -> Arc<ContentStore> {
+        Arc::clone(&self.content_store)
+    }
+
+    /// Path of the cached copy (and its chunk index) for a source path.
+    pub fn cache_path_for(&self, source_path: &str) -> PathBuf {
+        self.cache_root.join(source_path.trim_start_matches('/'))
+    }
+
+    /// Reads `[start, end]` (inclusive) of `source_path` by replaying the
+    /// chunks of its saved chunk index, or `None` if there is no index or any
+    /// part of the range isn't covered by it, so the caller can fall back to
+    /// a direct range fetch. A raw copy written by the parallel
+    /// range-download path has no index and is not read here.
+    pub async fn read_cached_range(
+        &self,
+        source_path: &str,
+        start: u64,
+        end: u64,
+    ) -> Option<Vec<u8>> {
+        let idx_path = index_path(&self.cache_path_for(source_path));
+        let index = FileIndex::load(&idx_path).ok()?;
+        read_chunks_range(&self.content_store, &index.chunks, start, end).await
+    }
+
+    /// Builds a stream of the logical bytes of `source_path`: if it's still
+    /// being cached, the stream reads up to the live write frontier and
+    /// blocks for more instead of erroring, so a client can consume the
+    /// file as it downloads; if it already finished, it reads straight
+    /// through the saved index, or — for files the parallel range-download
+    /// path cached, which never write one — directly from the raw cache
+    /// file. Returns `None` if nothing is cached yet.
+    pub async fn stream_cached(
+        self: &Arc<Self>,
+        source_path: &str,
+    ) -> Option<impl futures::Stream<Item = std::io::Result<bytes::Bytes>>> {
+        const STREAM_CHUNK: u64 = 256 * 1024;
+
+        let total_size = if let Some(progress) = self.active.read().get(source_path) {
+            progress.read().total_size.max(0) as u64
+        } else {
+            let idx_path = index_path(&self.cache_path_for(source_path));
+            match FileIndex::load(&idx_path) {
+                Ok(index) => index.logical_size(),
+                Err(_) => self.job_index.read().get(source_path)?.total_size.max(0) as u64,
+            }
+        };
+
+        let manager = Arc::clone(self);
+        let source_path = source_path.to_string();
+
+        Some(futures::stream::unfold(0u64, move |position| {
+            let manager = Arc::clone(&manager);
+            let source_path = source_path.clone();
+            async move {
+                if position >= total_size {
+                    return None;
+                }
+
+                loop {
+                    let active = manager.active.read().contains_key(&source_path);
+                    let cache_path = manager.cache_path_for(&source_path);
+                    let status = manager.write_status.read().get(&cache_path).cloned();
+
+                    let (frontier, readable) = match &status {
+                        Some(status) => {
+                            (status.flushed(), Readable::Chunks(status.chunks_snapshot()))
+                        }
+                        None => {
+                            let idx_path = index_path(&cache_path);
+                            match FileIndex::load(&idx_path) {
+                                // Job finished and its WriteStatus was torn
+                                // down: read straight from the saved index.
+                                Ok(index) => (total_size, Readable::Chunks(index.chunks)),
+                                // No index at all: the parallel range-download
+                                // path wrote (or is still writing) a raw file
+                                // instead. Once it's no longer active it's
+                                // done and the whole thing is safe to read;
+                                // while active, only the ranges it's recorded
+                                // as durably written, and only up to the
+                                // first gap, are.
+                                Err(_) if !active => (total_size, Readable::Raw),
+                                Err(_) => {
+                                    let frontier = manager
+                                        .job_index
+                                        .read()
+                                        .get(&source_path)
+                                        .map(|job| {
+                                            contiguous_frontier(
+                                                &job.completed_chunks,
+                                                manager.chunk_size as u64,
+                                                total_size,
+                                            )
+                                        })
+                                        .unwrap_or(position);
+                                    (frontier, Readable::Raw)
+                                }
+                            }
+                        }
+                    };
+
+                    if frontier > position {
+                        let end = (position + STREAM_CHUNK - 1)
+                            .min(frontier - 1)
+                            .min(total_size - 1);
+                        let data = match readable {
+                            Readable::Chunks(chunks) => {
+                                read_chunks_range(&manager.content_store, &chunks, position, end)
+                                    .await?
+                            }
+                            Readable::Raw => read_raw_range(&cache_path, position, end).await.ok()?,
+                        };
+                        return Some((Ok(bytes::Bytes::from(data)), end + 1));
+                    }
+
+                    if !active {
+                        return None;
+                    }
+
+                    match status {
+                        Some(status) => status.notified().await,
+                        // No WriteStatus to wait on (parallel download path,
+                        // or the CDC path hasn't registered one yet) — poll.
+                        None => tokio::time::sleep(std::time::Duration::from_millis(200)).await,
+                    }
+                }
+            }
+        }))
+    }
+
     pub async fn start_progress(
         &self,
         source_path: String,
@@ -32,13 +465,42 @@ impl CacheManager {
         let thread_count = threads.unwrap_or(1);
 
         // Get file size from storage backend
-        let total_size = if let Ok(size) = self.storage_backend.file_size(&source_path).await {
-            size
-        } else {
+        let file_size = self.storage_backend.file_size(&source_path).await.ok();
+        let total_size = match file_size {
+            Some(size) => size,
             // It's a directory, calculate size recursively
-            self.calculate_directory_size(&source_path).await
+            None => self.calculate_directory_size(&source_path).await,
         };
 
+        // A restart shouldn't re-cache a file that's already fully on disk,
+        // whether as a finished chunk index (CDC path) or a raw copy
+        // (parallel download path). Directories always recurse instead,
+        // since each file underneath is checked individually. A record left
+        // incomplete means an earlier run was cut off, and the raw file the
+        // parallel path preallocates already has its full length, so the
+        // size check can't be trusted then.
+        let interrupted = self
+            .job_index
+            .read()
+            .get(&source_path)
+            .is_some_and(|job| !job.complete);
+        if file_size.is_some()
+            && !interrupted
+            && Self::already_cached(&self.storage_backend, &source_path, &cache_path, total_size)
+                .await
+        {
+            self.persist_job(JobRecord {
+                source_path,
+                cache_path,
+                total_size,
+                cached_size: total_size,
+                complete: true,
+                completed_chunks: HashSet::new(),
+                chunk_size: self.chunk_size,
+            });
+            return Ok(());
+        }
+
         let progress = Arc::new(RwLock::new(CacheProgress {
             current_speed: 0.0,
             total_bytes_read: 0,
@@ -52,34 +514,102 @@ impl CacheManager {
             .write()
             .insert(source_path.clone(), Arc::clone(&progress));
 
+        // Keep the ranges an interrupted parallel run already wrote, so
+        // `cache_file_inner` can still skip them.
+        let completed_chunks = resumable_chunks(
+            self.job_index.read().get(&source_path),
+            &cache_path,
+            total_size.max(0) as u64,
+            self.chunk_size,
+        );
+        self.persist_job(JobRecord {
+            source_path: source_path.clone(),
+            cache_path: cache_path.clone(),
+            total_size,
+            cached_size: 0,
+            complete: false,
+            completed_chunks,
+            chunk_size: self.chunk_size,
+        });
+
         let progress_clone = Arc::clone(&progress);
         let source_path_clone = source_path.clone();
+        let cache_path_clone = cache_path.clone();
         let chunk_size = self.chunk_size;
         let active_clone = Arc::clone(&self.active);
         let storage_backend = Arc::clone(&self.storage_backend);
+        let content_store = Arc::clone(&self.content_store);
+        let evictor = self.evictor.clone();
+        let cache_root = self.cache_root.clone();
+        let write_status = Arc::clone(&self.write_status);
+        let job_index = Arc::clone(&self.job_index);
+        let job_index_path = Arc::clone(&self.job_index_path);
 
         tokio::spawn(async move {
-            if let Err(e) = Self::cache_file(
+            let result = Self::cache_file(
                 &storage_backend,
+                &content_store,
+                &write_status,
+                &job_index,
+                &job_index_path,
                 &source_path_clone,
                 cache_path,
                 &progress_clone,
                 chunk_size,
                 thread_count,
             )
-            .await
-            {
+            .await;
+            if let Err(e) = &result {
                 tracing::error!("Error caching file {:?}: {}", source_path_clone, e);
             }
 
             if let Some(progress) = active_clone.write().remove(&source_path_clone) {
                 progress.write().is_complete = true;
             }
+
+            // A failed job stays incomplete and keeps the ranges it already
+            // wrote, so the next precache resumes it.
+            let completed_chunks = match &result {
+                Ok(()) => HashSet::new(),
+                Err(_) => job_index
+                    .read()
+                    .get(&source_path_clone)
+                    .map(|job| job.completed_chunks.clone())
+                    .unwrap_or_default(),
+            };
+            job_index.write().upsert(JobRecord {
+                source_path: source_path_clone.clone(),
+                cache_path: cache_path_clone,
+                total_size,
+                cached_size: progress_clone.read().cached_size,
+                complete: result.is_ok(),
+                completed_chunks,
+                chunk_size,
+            });
+            if let Err(e) = job_index.read().save(&job_index_path) {
+                tracing::error!("Error persisting job index: {}", e);
+            }
+
+            if let Some(evictor) = evictor {
+                let protected = protected_paths(&active_clone, &cache_root);
+                if let Err(e) = evictor.evict_if_over_budget(&protected).await {
+                    tracing::error!("Error evicting cache after {:?}: {}", source_path_clone, e);
+                }
+            }
         });
 
         Ok(())
     }
 
+    /// Current on-disk cache size and configured budget, for display; both
+    /// are `0` when eviction is disabled.
+    pub fn cache_usage(&self) -> (i64, i64) {
+        match &self.evictor {
+            Some(evictor) => (evictor.total(), evictor.budget()),
+            None => (0, 0),
+        }
+    }
+
     pub async fn get_progress(&self, path: &str) -> Option<Arc<RwLock<CacheProgress>>> {
         self.active.read().get(path).cloned()
     }
@@ -98,6 +628,8 @@ impl CacheManager {
             total_size += progress.total_size;
         }
 
+        let (cache_total, cache_budget) = self.cache_usage();
+
         GlobalProgress {
             total_speed,
             overall_percent: if total_size > 0 {
@@ -107,6 +639,8 @@ impl CacheManager {
             },
             active_jobs,
             cached_size: total_bytes,
+            cache_total,
+            cache_budget,
         }
     }
 
@@ -131,19 +665,41 @@ impl CacheManager {
         })
     }
 
+    #[allow(clippy::too_many_arguments)]
     async fn cache_file(
         storage_backend: &Arc<dyn StorageBackend>,
+        content_store: &Arc<ContentStore>,
+        write_status: &Arc<RwLock<HashMap<PathBuf, Arc<WriteStatus>>>>,
+        job_index: &Arc<RwLock<JobIndex>>,
+        job_index_path: &Arc<PathBuf>,
         source_path: &str,
         cache_path: PathBuf,
         progress: &Arc<RwLock<CacheProgress>>,
         chunk_size: usize,
         threads: usize,
     ) -> Result<(), std::io::Error> {
-        Self::cache_file_inner(storage_backend, source_path, cache_path, progress, chunk_size, threads).await
+        Self::cache_file_inner(
+            storage_backend,
+            content_store,
+            write_status,
+            job_index,
+            job_index_path,
+            source_path,
+            cache_path,
+            progress,
+            chunk_size,
+            threads,
+        )
+        .await
     }
 
+    #[allow(clippy::too_many_arguments)]
     fn cache_file_inner<'a>(
         storage_backend: &'a Arc<dyn StorageBackend>,
+        content_store: &'a Arc<ContentStore>,
+        write_status: &'a Arc<RwLock<HashMap<PathBuf, Arc<WriteStatus>>>>,
+        job_index: &'a Arc<RwLock<JobIndex>>,
+        job_index_path: &'a Arc<PathBuf>,
         source_path: &'a str,
         cache_path: PathBuf,
         progress: &'a Arc<RwLock<CacheProgress>>,
@@ -153,71 +709,391 @@ impl CacheManager {
         Box::pin(async move {
             // Check if it's a directory
             let entry = storage_backend.metadata(source_path).await?;
-            
+
             if entry.is_dir {
                 // Create cache directory if it doesn't exist
                 tokio::fs::create_dir_all(&cache_path).await?;
-                
+
                 // Recursively cache all files in directory
                 let entries = storage_backend.list_dir(source_path).await?;
                 for entry in entries {
                     let sub_cache_path = cache_path.join(&entry.name);
-                    Self::cache_file(storage_backend, &entry.path, sub_cache_path, progress, chunk_size, in_threads).await?;
+                    Self::cache_file(
+                        storage_backend,
+                        content_store,
+                        write_status,
+                        job_index,
+                        job_index_path,
+                        &entry.path,
+                        sub_cache_path,
+                        progress,
+                        chunk_size,
+                        in_threads,
+                    )
+                    .await?;
                 }
                 return Ok(());
             }
 
-            // For regular files, read from storage backend and write to cache
-            // Create parent directory if it doesn't exist
+            // For regular files, create the parent cache directory if needed.
             if let Some(parent) = cache_path.parent() {
                 tokio::fs::create_dir_all(parent).await?;
             }
-            
-            let mut reader = storage_backend.open_file(source_path).await?;
-            let mut writer = tokio::fs::File::create(&cache_path).await?;
-            
-            let mut buffer = vec![0u8; chunk_size];
-            let mut total_bytes_read = 0i64;
-            let mut last_update = std::time::SystemTime::now();
 
-            loop {
-                match reader.read(&mut buffer).await {
-                    Ok(0) => break, // EOF
-                    Ok(n) => {
-                        // Write to cache file
-                        tokio::io::AsyncWriteExt::write_all(&mut writer, &buffer[..n]).await?;
-
-                        total_bytes_read += n as i64;
-                        let now = std::time::SystemTime::now();
-
-                        let time_passed = now
-                            .duration_since(last_update)
-                            .unwrap_or(std::time::Duration::from_secs(0));
-                        if time_passed >= std::time::Duration::from_secs(1) {
+            let total_size = entry.size.unwrap_or(0).max(0) as u64;
+
+            if in_threads > 1 && total_size > PARALLEL_THRESHOLD {
+                // A previous, interrupted run of the parallel path may have
+                // already durably written some of this file's fixed-size
+                // ranges; skip re-fetching those.
+                let resume_chunks = resumable_chunks(
+                    job_index.read().get(source_path),
+                    &cache_path,
+                    total_size,
+                    chunk_size,
+                );
+
+                return Self::cache_file_parallel(
+                    storage_backend,
+                    source_path,
+                    cache_path,
+                    progress,
+                    chunk_size,
+                    in_threads,
+                    total_size,
+                    resume_chunks,
+                    job_index,
+                    job_index_path,
+                )
+                .await;
+            }
+
+            let idx_path = index_path(&cache_path);
+            let existing_index = FileIndex::load(&idx_path).ok();
+
+            // If we've cached this file before, revalidate with a conditional
+            // GET instead of blindly re-downloading it.
+            let mut reader = match &existing_index {
+                Some(existing) if existing.logical_size() == total_size => {
+                    match storage_backend
+                        .open_file_conditional(
+                            source_path,
+                            existing.etag.as_deref(),
+                            Some(existing.last_modified),
+                        )
+                        .await?
+                    {
+                        crate::storage_backend::ConditionalFetch::NotModified => {
                             let mut progress_guard = progress.write();
-                            progress_guard.total_bytes_read += total_bytes_read;
-                            progress_guard.update_speed(total_bytes_read, now);
-                            progress_guard.cached_size += total_bytes_read;
-                            total_bytes_read = 0;
-                            last_update = now;
+                            progress_guard.total_bytes_read += total_size as i64;
+                            progress_guard.cached_size += total_size as i64;
+                            return Ok(());
                         }
+                        crate::storage_backend::ConditionalFetch::Modified(reader) => reader,
+                    }
+                }
+                _ => storage_backend.open_file(source_path).await?,
+            };
+
+            let status = Arc::new(WriteStatus::new());
+            write_status
+                .write()
+                .insert(cache_path.clone(), Arc::clone(&status));
+            let _status_guard = WriteStatusGuard {
+                map: Arc::clone(write_status),
+                cache_path: cache_path.clone(),
+                status: Arc::clone(&status),
+            };
+
+            let mut chunker = ContentDefinedChunker::new();
+            let mut index = FileIndex::default();
+            let mut offset = 0u64;
+            let mut last_update = std::time::SystemTime::now();
+            let mut read_buf = vec![0u8; chunk_size.max(64 * 1024)];
+
+            loop {
+                let n = reader.read(&mut read_buf).await?;
+                let produced = if n == 0 {
+                    std::mem::replace(&mut chunker, ContentDefinedChunker::new()).finish()
+                } else {
+                    chunker.push(&read_buf[..n])
+                };
+
+                for chunk in produced {
+                    let digest = crate::content_store::digest_hex(&chunk);
+                    let is_new = !content_store.has_chunk(&digest);
+                    let disk_len = content_store.put_chunk(&digest, &chunk).await?;
+
+                    let len = chunk.len() as u64;
+                    let chunk_ref = ChunkRef {
+                        digest,
+                        offset,
+                        len,
+                        disk_len,
+                    };
+                    index.chunks.push(chunk_ref.clone());
+                    status.push_chunk(chunk_ref);
+                    offset += len;
+
+                    let now = std::time::SystemTime::now();
+                    let mut progress_guard = progress.write();
+                    progress_guard.total_bytes_read += len as i64;
+                    if is_new {
+                        progress_guard.cached_size += disk_len as i64;
+                    }
+                    let time_passed = now
+                        .duration_since(last_update)
+                        .unwrap_or(std::time::Duration::from_secs(0));
+                    if time_passed >= std::time::Duration::from_secs(1) {
+                        progress_guard.update_speed(len as i64, now);
+                        last_update = now;
                     }
-                    Err(e) => return Err(e),
+                }
+
+                if n == 0 {
+                    break;
                 }
             }
 
-            // Flush and sync the file to disk
-            tokio::io::AsyncWriteExt::flush(&mut writer).await?;
-            writer.sync_all().await?;
+            index.etag = entry.etag.clone();
+            index.last_modified = entry.modified_time;
+            index.save(&idx_path)?;
 
-            if total_bytes_read > 0 {
-                let mut progress_guard = progress.write();
-                progress_guard.total_bytes_read += total_bytes_read;
-                progress_guard.update_speed(total_bytes_read, std::time::SystemTime::now());
-                progress_guard.cached_size += total_bytes_read;
+            // The source changed underneath an existing cache entry (the
+            // `Modified` branch above): `put_chunk` already counted a fresh
+            // reference for every chunk of the new index, so drop every
+            // reference the superseded index held, including chunks both
+            // share, or they'd sit in `store/` forever — `CacheEvictor` never
+            // walks `store/` directly, so an orphaned chunk is invisible to
+            // both size accounting and eviction.
+            if let Some(existing) = existing_index {
+                for chunk in &existing.chunks {
+                    content_store.release_chunk(&chunk.digest).await?;
+                }
             }
 
             Ok(())
         })
     }
+
+    /// Fetches a large file as fixed-size ranges spread across `threads`
+    /// worker tasks instead of through the sequential CDC path. Chunks land
+    /// out of order here, so unlike `cache_file_inner` this doesn't publish
+    /// a `WriteStatus`; `stream_cached` instead reads a job taking this path
+    /// up to the first gap in the ranges recorded in `job_index`, polling for
+    /// more. `resume_chunks` are range indices a
+    /// previous, interrupted run already wrote durably; each newly-completed
+    /// index is persisted to `job_index` as it lands, so a subsequent resume
+    /// can pick up from there too.
+    #[allow(clippy::too_many_arguments)]
+    async fn cache_file_parallel(
+        storage_backend: &Arc<dyn StorageBackend>,
+        source_path: &str,
+        cache_path: PathBuf,
+        progress: &Arc<RwLock<CacheProgress>>,
+        chunk_size: usize,
+        threads: usize,
+        total_size: u64,
+        resume_chunks: HashSet<usize>,
+        job_index: &Arc<RwLock<JobIndex>>,
+        job_index_path: &Arc<PathBuf>,
+    ) -> Result<(), std::io::Error> {
+        let num_chunks = total_size.div_ceil(chunk_size as u64).max(1) as usize;
+
+        {
+            let file = tokio::fs::OpenOptions::new()
+                .create(true)
+                .write(true)
+                .open(&cache_path)
+                .await?;
+            file.set_len(total_size).await?;
+        }
+
+        for &chunk_index in &resume_chunks {
+            let start = chunk_index as u64 * chunk_size as u64;
+            let end = (start + chunk_size as u64 - 1).min(total_size.saturating_sub(1));
+            let mut progress_guard = progress.write();
+            progress_guard.total_bytes_read += (end - start + 1) as i64;
+            progress_guard.cached_size += (end - start + 1) as i64;
+        }
+
+        let completed = Arc::new(RwLock::new(resume_chunks.clone()));
+        let semaphore = Arc::new(Semaphore::new(threads));
+        let mut workers = Vec::with_capacity(num_chunks);
+
+        for chunk_index in 0..num_chunks {
+            if resume_chunks.contains(&chunk_index) {
+                continue;
+            }
+
+            let storage_backend = Arc::clone(storage_backend);
+            let source_path_owned = source_path.to_string();
+            let cache_path = cache_path.clone();
+            let cache_path_for_record = cache_path.clone();
+            let progress = Arc::clone(progress);
+            let semaphore = Arc::clone(&semaphore);
+            let completed = Arc::clone(&completed);
+            let job_index = Arc::clone(job_index);
+            let job_index_path = Arc::clone(job_index_path);
+
+            let start = chunk_index as u64 * chunk_size as u64;
+            let end = (start + chunk_size as u64 - 1).min(total_size - 1);
+
+            workers.push(tokio::spawn(async move {
+                let _permit = semaphore.acquire_owned().await.unwrap();
+
+                let mut reader = storage_backend
+                    .open_file_range(&source_path_owned, start, Some(end))
+                    .await?;
+
+                let mut buf = Vec::with_capacity((end - start + 1) as usize);
+                reader.read_to_end(&mut buf).await?;
+
+                let written = tokio::task::spawn_blocking(move || -> std::io::Result<usize> {
+                    let file = std::fs::OpenOptions::new().write(true).open(&cache_path)?;
+                    file.write_at(&buf, start)?;
+                    Ok(buf.len())
+                })
+                .await
+                .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))??;
+
+                let cached_size = {
+                    let mut progress_guard = progress.write();
+                    progress_guard.total_bytes_read += written as i64;
+                    progress_guard.cached_size += written as i64;
+                    progress_guard.update_speed(written as i64, std::time::SystemTime::now());
+                    progress_guard.cached_size
+                };
+
+                let completed_chunks = {
+                    let mut completed = completed.write();
+                    completed.insert(chunk_index);
+                    completed.clone()
+                };
+                job_index.write().upsert(JobRecord {
+                    source_path: source_path_owned,
+                    cache_path: cache_path_for_record,
+                    total_size: total_size as i64,
+                    cached_size,
+                    complete: false,
+                    completed_chunks,
+                    chunk_size,
+                });
+                if let Err(e) = job_index.read().save(&job_index_path) {
+                    tracing::error!("Error persisting job index: {}", e);
+                }
+
+                Ok::<(), std::io::Error>(())
+            }));
+        }
+
+        for worker in workers {
+            worker
+                .await
+                .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))??;
+        }
+
+        let file = std::fs::OpenOptions::new().write(true).open(&cache_path)?;
+        file.sync_all()?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::content_store::digest_hex;
+    use crate::test_support::temp_cache_root;
+
+    #[tokio::test]
+    async fn read_chunks_range_replays_a_partial_overlap() {
+        let root = temp_cache_root("read-chunks-range");
+        let store = ContentStore::new(&root).unwrap();
+
+        let data_a = b"0123456789".to_vec();
+        let data_b = b"abcdefghij".to_vec();
+        let digest_a = digest_hex(&data_a);
+        let digest_b = digest_hex(&data_b);
+        let disk_len_a = store.put_chunk(&digest_a, &data_a).await.unwrap();
+        let disk_len_b = store.put_chunk(&digest_b, &data_b).await.unwrap();
+
+        let chunks = vec![
+            ChunkRef { digest: digest_a, offset: 0, len: 10, disk_len: disk_len_a },
+            ChunkRef { digest: digest_b, offset: 10, len: 10, disk_len: disk_len_b },
+        ];
+
+        // Range straddles both chunks.
+        let data = read_chunks_range(&store, &chunks, 5, 14).await.unwrap();
+        assert_eq!(data, b"56789abcde");
+
+        // Range past what's covered by the chunk list isn't satisfiable.
+        assert!(read_chunks_range(&store, &chunks, 15, 25).await.is_none());
+
+        let _ = std::fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn contiguous_frontier_stops_at_the_first_gap() {
+        let mut completed = HashSet::new();
+        completed.insert(0);
+        completed.insert(1);
+        // index 2 missing
+        completed.insert(3);
+
+        // Two 100-byte ranges landed contiguously from the start; the third
+        // is still missing, so the frontier can't advance past it even
+        // though a later range (index 3) already landed out of order.
+        assert_eq!(contiguous_frontier(&completed, 100, 400), 200);
+    }
+
+    fn job_record(cache_path: &Path, total_size: i64, chunk_size: usize, completed: &[usize]) -> JobRecord {
+        JobRecord {
+            source_path: "irrelevant".to_string(),
+            cache_path: cache_path.to_path_buf(),
+            total_size,
+            cached_size: 0,
+            complete: false,
+            completed_chunks: completed.iter().copied().collect(),
+            chunk_size,
+        }
+    }
+
+    #[test]
+    fn resumable_chunks_reuses_a_matching_job() {
+        let cache_path = PathBuf::from("/cache/a.bin");
+        let job = job_record(&cache_path, 1000, 64, &[0, 1, 2]);
+
+        let resumed = resumable_chunks(Some(&job), &cache_path, 1000, 64);
+        assert_eq!(resumed, [0, 1, 2].into_iter().collect());
+    }
+
+    #[test]
+    fn resumable_chunks_discards_a_chunk_size_mismatch() {
+        let cache_path = PathBuf::from("/cache/a.bin");
+        // Persisted under a 64-byte chunk size; this run uses 128 — reusing
+        // the old indices would write fetched bytes at the wrong offsets.
+        let job = job_record(&cache_path, 1000, 64, &[0, 1, 2]);
+
+        let resumed = resumable_chunks(Some(&job), &cache_path, 1000, 128);
+        assert!(resumed.is_empty());
+    }
+
+    #[test]
+    fn resumable_chunks_discards_a_total_size_mismatch() {
+        let cache_path = PathBuf::from("/cache/a.bin");
+        let job = job_record(&cache_path, 1000, 64, &[0, 1, 2]);
+
+        let resumed = resumable_chunks(Some(&job), &cache_path, 2000, 64);
+        assert!(resumed.is_empty());
+    }
+
+    #[test]
+    fn contiguous_frontier_caps_at_total_size() {
+        let mut completed = HashSet::new();
+        completed.insert(0);
+        completed.insert(1);
+
+        assert_eq!(contiguous_frontier(&completed, 100, 150), 150);
+    }
 }
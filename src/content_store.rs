@@ -0,0 +1,397 @@
+//! Content-addressed, deduplicated chunk storage.
+//!
+//! Incoming file streams are split into content-defined chunks with a
+//! buzhash-style rolling hash, so that identical runs of bytes land in
+//! identical chunks regardless of what file they came from or where the
+//! surrounding bytes shifted. Each chunk is stored once, keyed by its
+//! SHA-256 digest, under `store/<first 2 hex chars>/<digest>`. A cached
+//! file is represented as an ordered [`FileIndex`] of [`ChunkRef`]s that
+//! can be replayed to reconstruct its logical bytes.
+//!
+//! Each chunk is stored as either `Plain` (raw bytes) or `Compressed`
+//! (zstd), distinguished by a `.zst` suffix; it is `Compressed` only when
+//! zstd shrinks it below `COMPRESSION_RATIO_THRESHOLD` of its raw size —
+//! the same split Garage uses for its `DataBlock` storage.
+
+use parking_lot::RwLock;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Target average chunk size (1 MiB); `CDC_MASK` is chosen so that a
+/// uniformly random rolling hash crosses this boundary about once per
+/// `CDC_TARGET` bytes.
+const CDC_TARGET: usize = 1 << 20;
+const CDC_MASK: u64 = (1 << 20) - 1;
+/// Skip boundary tests until this many bytes have accumulated, so a run of
+/// unlucky hash hits can't produce tiny chunks.
+const CDC_MIN: usize = CDC_TARGET / 4;
+/// Force a cut here even if no boundary hash was found, to bound variance.
+const CDC_MAX: usize = CDC_TARGET * 4;
+/// Width of the rolling hash window, in bytes.
+const CDC_WINDOW: usize = 64;
+
+/// zstd level used to probe (and, if it pays off, store) chunk compression.
+/// Low enough to be cheap on every chunk write, not tuned for ratio.
+const COMPRESSION_LEVEL: i32 = 3;
+/// A chunk is stored `Compressed` only if doing so shrinks it below this
+/// fraction of its raw size; otherwise the compression overhead isn't worth
+/// the decode cost and it's stored `Plain`.
+const COMPRESSION_RATIO_THRESHOLD: f64 = 0.9;
+
+/// One chunk reference within a cached file's index.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChunkRef {
+    pub digest: String,
+    pub offset: u64,
+    pub len: u64,
+    /// Bytes the chunk actually occupies in the store, which is less than
+    /// `len` when it was compressible enough to store as `Compressed`.
+    #[serde(default)]
+    pub disk_len: u64,
+}
+
+/// Ordered list of chunk references that reconstructs a cached file's bytes.
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct FileIndex {
+    pub chunks: Vec<ChunkRef>,
+    /// Validators from the source as of the last successful fetch, used to
+    /// skip re-downloading an unchanged file on the next precache.
+    #[serde(default)]
+    pub etag: Option<String>,
+    #[serde(default)]
+    pub last_modified: f64,
+}
+
+impl FileIndex {
+    /// Logical (uncompressed, un-deduplicated) size of the file this index describes.
+    pub fn logical_size(&self) -> u64 {
+        self.chunks.iter().map(|c| c.len).sum()
+    }
+
+    /// On-disk (compressed, un-deduplicated) footprint of this file's chunks.
+    pub fn disk_size(&self) -> u64 {
+        self.chunks.iter().map(|c| c.disk_len).sum()
+    }
+
+    pub fn load(path: &Path) -> io::Result<Self> {
+        let bytes = std::fs::read(path)?;
+        serde_json::from_slice(&bytes)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let bytes = serde_json::to_vec(self).map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
+        std::fs::write(path, bytes)
+    }
+}
+
+/// Content-addressed chunk store rooted at `<cache_path>/store`, with
+/// reference counts so eviction can reclaim only unshared chunks.
+pub struct ContentStore {
+    root: PathBuf,
+    refcounts: RwLock<HashMap<String, u32>>,
+}
+
+impl ContentStore {
+    pub fn new(cache_root: &Path) -> io::Result<Self> {
+        let root = cache_root.join("store");
+        std::fs::create_dir_all(&root)?;
+
+        let refcounts = std::fs::read(root.join("refcounts.json"))
+            .ok()
+            .and_then(|bytes| serde_json::from_slice(&bytes).ok())
+            .unwrap_or_default();
+
+        Ok(Self {
+            root,
+            refcounts: RwLock::new(refcounts),
+        })
+    }
+
+    /// Path a chunk would have on disk if stored uncompressed (`Plain`).
+    fn plain_path(&self, digest: &str) -> PathBuf {
+        self.root.join(&digest[..2]).join(digest)
+    }
+
+    /// Path a chunk would have on disk if stored zstd-compressed (`Compressed`).
+    fn compressed_path(&self, digest: &str) -> PathBuf {
+        self.plain_path(digest).with_extension("zst")
+    }
+
+    /// Finds whichever variant of `digest` is actually on disk, if any.
+    fn resolve_chunk(&self, digest: &str) -> Option<(PathBuf, bool)> {
+        let compressed = self.compressed_path(digest);
+        if compressed.exists() {
+            return Some((compressed, true));
+        }
+        let plain = self.plain_path(digest);
+        if plain.exists() {
+            return Some((plain, false));
+        }
+        None
+    }
+
+    pub fn has_chunk(&self, digest: &str) -> bool {
+        self.resolve_chunk(digest).is_some()
+    }
+
+    /// Write `data` under `digest` if it isn't already stored, and bump its
+    /// reference count either way. Stores it zstd-compressed (`Compressed`)
+    /// when that beats [`COMPRESSION_RATIO_THRESHOLD`], or as-is (`Plain`)
+    /// otherwise — mirroring Garage's `DataBlock::Plain`/`DataBlock::Compressed`
+    /// split. Returns the number of bytes the chunk occupies on disk.
+    pub async fn put_chunk(&self, digest: &str, data: &[u8]) -> io::Result<u64> {
+        if let Some((path, _)) = self.resolve_chunk(digest) {
+            *self.refcounts.write().entry(digest.to_string()).or_insert(0) += 1;
+            self.persist_refcounts()?;
+            return Ok(tokio::fs::metadata(&path).await?.len());
+        }
+
+        let compressed = zstd::stream::encode_all(data, COMPRESSION_LEVEL)?;
+        let compress = (compressed.len() as f64) < (data.len() as f64) * COMPRESSION_RATIO_THRESHOLD;
+        let (path, bytes): (PathBuf, &[u8]) = if compress {
+            (self.compressed_path(digest), &compressed)
+        } else {
+            (self.plain_path(digest), data)
+        };
+
+        if let Some(parent) = path.parent() {
+            tokio::fs::create_dir_all(parent).await?;
+        }
+        let tmp = path.with_extension("tmp");
+        tokio::fs::write(&tmp, bytes).await?;
+        tokio::fs::rename(&tmp, &path).await?;
+
+        *self.refcounts.write().entry(digest.to_string()).or_insert(0) += 1;
+        self.persist_refcounts()?;
+        Ok(bytes.len() as u64)
+    }
+
+    /// Drop one reference to `digest`, deleting the chunk once nothing shares
+    /// it. Returns whether the chunk was actually deleted, so callers that
+    /// credit freed space (e.g. [`CacheEvictor`](crate::cache_evictor::CacheEvictor))
+    /// don't count bytes still on disk because another file shares the chunk.
+    pub async fn release_chunk(&self, digest: &str) -> io::Result<bool> {
+        let should_delete = {
+            let mut refcounts = self.refcounts.write();
+            match refcounts.get_mut(digest) {
+                Some(count) if *count > 1 => {
+                    *count -= 1;
+                    false
+                }
+                Some(_) => {
+                    refcounts.remove(digest);
+                    true
+                }
+                None => false,
+            }
+        };
+
+        if should_delete {
+            if let Some((path, _)) = self.resolve_chunk(digest) {
+                let _ = tokio::fs::remove_file(path).await;
+            }
+        }
+
+        self.persist_refcounts()?;
+        Ok(should_delete)
+    }
+
+    pub async fn read_chunk(&self, digest: &str) -> io::Result<Vec<u8>> {
+        let (path, compressed) = self
+            .resolve_chunk(digest)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, digest.to_string()))?;
+        let bytes = tokio::fs::read(&path).await?;
+        if compressed {
+            zstd::stream::decode_all(bytes.as_slice())
+        } else {
+            Ok(bytes)
+        }
+    }
+
+    fn persist_refcounts(&self) -> io::Result<()> {
+        let snapshot = self.refcounts.read().clone();
+        let bytes = serde_json::to_vec(&snapshot).map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
+        std::fs::write(self.root.join("refcounts.json"), bytes)
+    }
+}
+
+pub fn digest_hex(data: &[u8]) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(data);
+    hex::encode(hasher.finalize())
+}
+
+/// Splits a byte stream into content-defined chunks by feeding it
+/// incrementally through [`push`](Self::push), then draining the final
+/// partial chunk with [`finish`](Self::finish).
+pub struct ContentDefinedChunker {
+    buffer: Vec<u8>,
+}
+
+impl ContentDefinedChunker {
+    pub fn new() -> Self {
+        Self { buffer: Vec::new() }
+    }
+
+    /// Feed more bytes in, returning any chunks that can now be cut.
+    pub fn push(&mut self, data: &[u8]) -> Vec<Vec<u8>> {
+        self.buffer.extend_from_slice(data);
+        self.drain_boundaries()
+    }
+
+    /// Signal end of stream, returning the final (possibly short) chunk.
+    pub fn finish(mut self) -> Vec<Vec<u8>> {
+        let mut chunks = self.drain_boundaries();
+        if !self.buffer.is_empty() {
+            chunks.push(std::mem::take(&mut self.buffer));
+        }
+        chunks
+    }
+
+    fn drain_boundaries(&mut self) -> Vec<Vec<u8>> {
+        let mut chunks = Vec::new();
+        while let Some(boundary) = find_boundary(&self.buffer) {
+            chunks.push(self.buffer.drain(..boundary).collect());
+        }
+        chunks
+    }
+}
+
+/// Finds the next content-defined cut point in `buffer`, if one exists yet.
+/// Requires at least `CDC_MIN` bytes before testing for a hash boundary, and
+/// forces a cut at `CDC_MAX` bytes to bound worst-case chunk size.
+fn find_boundary(buffer: &[u8]) -> Option<usize> {
+    if buffer.len() < CDC_MIN {
+        return None;
+    }
+
+    let scan_end = buffer.len().min(CDC_MAX);
+    let mut hash: u64 = 0;
+
+    for i in 0..scan_end {
+        hash = roll(hash, buffer, i);
+        if i + 1 >= CDC_MIN && hash & CDC_MASK == 0 {
+            return Some(i + 1);
+        }
+    }
+
+    if scan_end >= CDC_MAX {
+        Some(CDC_MAX)
+    } else {
+        None
+    }
+}
+
+/// Buzhash-style rolling hash over a trailing `CDC_WINDOW`-byte window:
+/// each byte entering the window is hashed in, and the byte leaving the
+/// window (`CDC_WINDOW` bytes back) is hashed back out.
+fn roll(mut hash: u64, buffer: &[u8], i: usize) -> u64 {
+    hash = hash.rotate_left(1) ^ GEAR[buffer[i] as usize];
+
+    if i >= CDC_WINDOW {
+        let leaving = buffer[i - CDC_WINDOW];
+        hash ^= GEAR[leaving as usize].rotate_left(CDC_WINDOW as u32 % 64);
+    }
+
+    hash
+}
+
+/// Pseudo-random per-byte-value table used by the rolling hash, generated at
+/// compile time so no runtime initialization or external dependency is needed.
+static GEAR: [u64; 256] = gear_table();
+
+const fn splitmix64(x: u64) -> u64 {
+    let x = x.wrapping_add(0x9E3779B97F4A7C15);
+    let mut z = x;
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D049BB133111EB);
+    z ^ (z >> 31)
+}
+
+const fn gear_table() -> [u64; 256] {
+    let mut table = [0u64; 256];
+    let mut seed = 0x2545F4914F6CDD1Du64;
+    let mut i = 0;
+    while i < 256 {
+        seed = splitmix64(seed.wrapping_add(i as u64));
+        table[i] = seed;
+        i += 1;
+    }
+    table
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::temp_cache_root;
+
+    #[test]
+    fn find_boundary_requires_minimum_bytes() {
+        let buffer = vec![0u8; CDC_MIN - 1];
+        assert_eq!(find_boundary(&buffer), None);
+    }
+
+    #[test]
+    fn chunker_reassembles_input_and_respects_size_bounds() {
+        // Deterministic pseudo-random bytes, long enough to cut several
+        // content-defined boundaries.
+        let mut data = Vec::with_capacity(CDC_MAX * 6);
+        let mut state = 0x1234_5678_9abc_def0_u64;
+        for _ in 0..data.capacity() {
+            state = state.wrapping_mul(6364136223846793005).wrapping_add(1);
+            data.push((state >> 56) as u8);
+        }
+
+        let mut chunker = ContentDefinedChunker::new();
+        let mut chunks = chunker.push(&data);
+        chunks.extend(chunker.finish());
+
+        assert!(chunks.len() > 1, "expected more than one chunk to be cut");
+
+        let reassembled: Vec<u8> = chunks.iter().flatten().copied().collect();
+        assert_eq!(reassembled, data);
+
+        for (i, chunk) in chunks.iter().enumerate() {
+            assert!(chunk.len() <= CDC_MAX, "chunk exceeded CDC_MAX");
+            if i + 1 < chunks.len() {
+                assert!(
+                    chunk.len() >= CDC_MIN,
+                    "non-final chunk {} shorter than CDC_MIN: {}",
+                    i,
+                    chunk.len()
+                );
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn release_chunk_only_deletes_once_unshared() {
+        let root = temp_cache_root("refcounts");
+        let store = ContentStore::new(&root).unwrap();
+
+        let data = b"some chunk bytes shared by two cached files".to_vec();
+        let digest = digest_hex(&data);
+
+        // Two files reference the same chunk.
+        store.put_chunk(&digest, &data).await.unwrap();
+        store.put_chunk(&digest, &data).await.unwrap();
+        assert!(store.has_chunk(&digest));
+
+        // First file releases its reference: still shared, nothing deleted.
+        assert!(!store.release_chunk(&digest).await.unwrap());
+        assert!(store.has_chunk(&digest));
+
+        // Second (last) file releases its reference: now it's actually gone.
+        assert!(store.release_chunk(&digest).await.unwrap());
+        assert!(!store.has_chunk(&digest));
+
+        let _ = std::fs::remove_dir_all(&root);
+    }
+}
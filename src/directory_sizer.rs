@@ -1,11 +1,24 @@
 use parking_lot::RwLock;
 use std::collections::HashMap;
 use std::os::linux::fs::MetadataExt;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::time::{Duration, SystemTime};
 use std::pin::Pin;
 use std::future::Future;
 
+/// Files are cached as a chunk index rather than a raw copy; if one exists
+/// for `path`, report the on-disk footprint of its chunks (which may be
+/// compressed and/or shared with other files) rather than stat-ing a cache
+/// file that no longer holds the bytes directly.
+fn indexed_size(path: &Path) -> Option<i64> {
+    let mut idx_name = path.as_os_str().to_owned();
+    idx_name.push(".idx");
+    let idx_path = PathBuf::from(idx_name);
+    crate::content_store::FileIndex::load(&idx_path)
+        .ok()
+        .map(|index| index.disk_size() as i64)
+}
+
 struct SizeCache {
     size: i64,
     timestamp: SystemTime,
@@ -25,9 +38,13 @@ impl DirectorySizer {
     }
     
     pub async fn get_allocated_size(&self, path: &PathBuf) -> i64 {
+        if let Some(size) = indexed_size(path) {
+            return size;
+        }
+
         let abs_path = path.canonicalize().unwrap_or_else(|_| path.clone());
         let path_str = abs_path.to_string_lossy().to_string();
-        
+
         // Check cache first
         if let Some(size) = self.check_cache(&path_str) {
             return size;
@@ -100,7 +117,18 @@ impl DirectorySizer {
 
                 if let Ok(metadata) = entry.metadata().await {
                     let size = if metadata.is_file() {
-                        metadata.st_blocks() as i64 * 512 as i64
+                        // A chunk-indexed file is stored on disk only as its
+                        // `.idx` sidecar (the bytes themselves live in the
+                        // content store), so a raw stat here would report the
+                        // tiny index file's own size instead of the indexed
+                        // chunks' on-disk footprint.
+                        if path.extension().and_then(|e| e.to_str()) == Some("idx") {
+                            crate::content_store::FileIndex::load(&path)
+                                .map(|index| index.disk_size() as i64)
+                                .unwrap_or(0)
+                        } else {
+                            metadata.st_blocks() as i64 * 512 as i64
+                        }
                     } else {
                         self.calculate_size_inner(&path).await
                     };
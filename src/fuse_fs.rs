@@ -0,0 +1,303 @@
+//! Presents the browsed `StorageBackend` tree as a read-only FUSE mount,
+//! serving reads from already-cached chunks and transparently range-fetching
+//! the touched region of anything not yet local, while also kicking off a
+//! background precache of the whole file so later reads of it land on disk
+//! instead of hitting the remote again (read-through caching).
+
+use crate::cache_manager::CacheManager;
+use crate::storage_backend::StorageBackend;
+use fuser::{
+    FileAttr, FileType, Filesystem, MountOption, ReplyAttr, ReplyData, ReplyDirectory,
+    ReplyEntry, Request,
+};
+use parking_lot::RwLock;
+use std::collections::HashMap;
+use std::ffi::OsStr;
+use std::sync::Arc;
+use std::time::{Duration, UNIX_EPOCH};
+
+const TTL: Duration = Duration::from_secs(1);
+const ROOT_INO: u64 = 1;
+
+/// Maps FUSE inodes to the logical storage paths they represent, assigning
+/// new inodes lazily as `lookup`/`readdir` discover entries.
+struct InodeTable {
+    path_to_ino: HashMap<String, u64>,
+    ino_to_path: HashMap<u64, String>,
+    next_ino: u64,
+}
+
+impl InodeTable {
+    fn new() -> Self {
+        let mut path_to_ino = HashMap::new();
+        let mut ino_to_path = HashMap::new();
+        path_to_ino.insert("/".to_string(), ROOT_INO);
+        ino_to_path.insert(ROOT_INO, "/".to_string());
+
+        Self {
+            path_to_ino,
+            ino_to_path,
+            next_ino: ROOT_INO + 1,
+        }
+    }
+
+    fn ino_for(&mut self, path: &str) -> u64 {
+        if let Some(&ino) = self.path_to_ino.get(path) {
+            return ino;
+        }
+
+        let ino = self.next_ino;
+        self.next_ino += 1;
+        self.path_to_ino.insert(path.to_string(), ino);
+        self.ino_to_path.insert(ino, path.to_string());
+        ino
+    }
+
+    fn path_for(&self, ino: u64) -> Option<String> {
+        self.ino_to_path.get(&ino).cloned()
+    }
+}
+
+pub struct PrecacheFuse {
+    storage_backend: Arc<dyn StorageBackend>,
+    cache_manager: Arc<CacheManager>,
+    rt: tokio::runtime::Handle,
+    inodes: RwLock<InodeTable>,
+}
+
+impl PrecacheFuse {
+    pub fn new(
+        storage_backend: Arc<dyn StorageBackend>,
+        cache_manager: Arc<CacheManager>,
+        rt: tokio::runtime::Handle,
+    ) -> Self {
+        Self {
+            storage_backend,
+            cache_manager,
+            rt,
+            inodes: RwLock::new(InodeTable::new()),
+        }
+    }
+
+    /// Blocking-mounts this filesystem at `mountpoint`. Call from a dedicated
+    /// thread, since `fuser::mount2` doesn't return until the mount is torn down.
+    pub fn mount(self, mountpoint: &std::path::Path) -> std::io::Result<()> {
+        let options = vec![MountOption::RO, MountOption::FSName("rclone-precache".to_string())];
+        fuser::mount2(self, mountpoint, &options)
+    }
+
+    fn child_path(parent: &str, name: &str) -> String {
+        if parent == "/" {
+            format!("/{}", name)
+        } else {
+            format!("{}/{}", parent, name)
+        }
+    }
+
+    /// Starts a precache job for `path` in the background unless one is
+    /// already running, so a cache-miss read turns this into read-through
+    /// caching rather than a plain per-read passthrough. Fire-and-forget:
+    /// the caller gets its answer from the direct range fetch regardless of
+    /// how long the full-file precache takes.
+    fn start_background_precache(&self, path: &str) {
+        if self.rt.block_on(self.cache_manager.get_progress(path)).is_some() {
+            return;
+        }
+
+        let cache_manager = Arc::clone(&self.cache_manager);
+        let cache_path = self.cache_manager.cache_path_for(path);
+        let path = path.to_string();
+        self.rt.spawn(async move {
+            if let Err(e) = cache_manager.start_progress(path.clone(), cache_path, None).await {
+                tracing::error!("Error starting background precache for {:?}: {}", path, e);
+            }
+        });
+    }
+
+    fn attr_for(ino: u64, is_dir: bool, size: u64, modified_time: f64) -> FileAttr {
+        let mtime = UNIX_EPOCH + Duration::from_secs_f64(modified_time.max(0.0));
+        FileAttr {
+            ino,
+            size,
+            blocks: size.div_ceil(512),
+            atime: mtime,
+            mtime,
+            ctime: mtime,
+            crtime: mtime,
+            kind: if is_dir {
+                FileType::Directory
+            } else {
+                FileType::RegularFile
+            },
+            perm: if is_dir { 0o755 } else { 0o444 },
+            nlink: 1,
+            uid: 0,
+            gid: 0,
+            rdev: 0,
+            blksize: 512,
+            flags: 0,
+        }
+    }
+}
+
+impl Filesystem for PrecacheFuse {
+    fn lookup(&mut self, _req: &Request, parent: u64, name: &OsStr, reply: ReplyEntry) {
+        let Some(parent_path) = self.inodes.read().path_for(parent) else {
+            reply.error(libc::ENOENT);
+            return;
+        };
+        let Some(name) = name.to_str() else {
+            reply.error(libc::EINVAL);
+            return;
+        };
+
+        let entries = match self.rt.block_on(self.storage_backend.list_dir(&parent_path)) {
+            Ok(entries) => entries,
+            Err(_) => {
+                reply.error(libc::EIO);
+                return;
+            }
+        };
+
+        match entries.into_iter().find(|e| e.name == name) {
+            Some(entry) => {
+                let path = Self::child_path(&parent_path, &entry.name);
+                let ino = self.inodes.write().ino_for(&path);
+                let attr = Self::attr_for(
+                    ino,
+                    entry.is_dir,
+                    entry.size.unwrap_or(0).max(0) as u64,
+                    entry.modified_time,
+                );
+                reply.entry(&TTL, &attr, 0);
+            }
+            None => reply.error(libc::ENOENT),
+        }
+    }
+
+    fn getattr(&mut self, _req: &Request, ino: u64, _fh: Option<u64>, reply: ReplyAttr) {
+        let Some(path) = self.inodes.read().path_for(ino) else {
+            reply.error(libc::ENOENT);
+            return;
+        };
+
+        match self.rt.block_on(self.storage_backend.metadata(&path)) {
+            Ok(entry) => {
+                let attr = Self::attr_for(
+                    ino,
+                    entry.is_dir,
+                    entry.size.unwrap_or(0).max(0) as u64,
+                    entry.modified_time,
+                );
+                reply.attr(&TTL, &attr);
+            }
+            Err(_) => reply.error(libc::ENOENT),
+        }
+    }
+
+    fn readdir(
+        &mut self,
+        _req: &Request,
+        ino: u64,
+        _fh: u64,
+        offset: i64,
+        mut reply: ReplyDirectory,
+    ) {
+        let Some(path) = self.inodes.read().path_for(ino) else {
+            reply.error(libc::ENOENT);
+            return;
+        };
+
+        let entries = match self.rt.block_on(self.storage_backend.list_dir(&path)) {
+            Ok(entries) => entries,
+            Err(_) => {
+                reply.error(libc::EIO);
+                return;
+            }
+        };
+
+        let mut listing = vec![
+            (ino, FileType::Directory, ".".to_string()),
+            (ino, FileType::Directory, "..".to_string()),
+        ];
+        for entry in entries {
+            let child_path = Self::child_path(&path, &entry.name);
+            let child_ino = self.inodes.write().ino_for(&child_path);
+            let kind = if entry.is_dir {
+                FileType::Directory
+            } else {
+                FileType::RegularFile
+            };
+            listing.push((child_ino, kind, entry.name));
+        }
+
+        for (i, (ino, kind, name)) in listing.into_iter().enumerate().skip(offset as usize) {
+            if reply.add(ino, (i + 1) as i64, kind, &name) {
+                break;
+            }
+        }
+        reply.ok();
+    }
+
+    fn read(
+        &mut self,
+        _req: &Request,
+        ino: u64,
+        _fh: u64,
+        offset: i64,
+        size: u32,
+        _flags: i32,
+        _lock_owner: Option<u64>,
+        reply: ReplyData,
+    ) {
+        let Some(path) = self.inodes.read().path_for(ino) else {
+            reply.error(libc::ENOENT);
+            return;
+        };
+
+        if size == 0 {
+            reply.data(&[]);
+            return;
+        }
+
+        let start = offset as u64;
+        let end = start + size as u64 - 1;
+
+        if let Some(data) = self
+            .rt
+            .block_on(self.cache_manager.read_cached_range(&path, start, end))
+        {
+            reply.data(&data);
+            return;
+        }
+
+        // Miss: serve this read straight from the remote so the caller
+        // isn't blocked on a full-file precache, but also kick one off (if
+        // one isn't already running) so the touched file lands in the cache
+        // and later reads of it — including this same range — are served
+        // from disk instead of re-fetching the remote every time.
+        self.start_background_precache(&path);
+
+        match self
+            .rt
+            .block_on(read_range_direct(&self.storage_backend, &path, start, end))
+        {
+            Ok(data) => reply.data(&data),
+            Err(_) => reply.error(libc::EIO),
+        }
+    }
+}
+
+async fn read_range_direct(
+    storage_backend: &Arc<dyn StorageBackend>,
+    path: &str,
+    start: u64,
+    end: u64,
+) -> std::io::Result<Vec<u8>> {
+    use tokio::io::AsyncReadExt;
+
+    let mut reader = storage_backend.open_file_range(path, start, Some(end)).await?;
+    let mut buf = Vec::with_capacity((end - start + 1) as usize);
+    reader.read_to_end(&mut buf).await?;
+    Ok(buf)
+}
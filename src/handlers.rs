@@ -23,4 +23,18 @@ pub async fn handle_cache_progress(
 ) -> Result<HttpResponse> {
     let progress = server.get_cache_progress(&path).await?;
     Ok(HttpResponse::Ok().json(progress))
+}
+
+/// Streams a file's bytes, reading through a still-in-progress precache job
+/// instead of making the caller wait for it to finish.
+pub async fn handle_stream(
+    path: web::Path<String>,
+    server: web::Data<Server>,
+) -> Result<HttpResponse> {
+    match server.stream_cached(&path).await {
+        Some(stream) => Ok(HttpResponse::Ok()
+            .content_type("application/octet-stream")
+            .streaming(stream)),
+        None => Ok(HttpResponse::NotFound().json("Not cached")),
+    }
 }
\ No newline at end of file
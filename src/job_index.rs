@@ -0,0 +1,166 @@
+//! Persisted record of in-flight and completed precache jobs, so a server
+//! restart can tell a finished job from one it still needs to (re)run
+//! instead of losing all progress and starting over.
+//!
+//! Follows the bingus-blog approach: serialize with serde, then write
+//! through a `zstd` encoder, guarded by a leading version integer so an
+//! incompatible on-disk schema is discarded rather than misread.
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Bumped whenever `JobRecord`'s on-disk shape changes incompatibly.
+const JOB_INDEX_VERSION: u32 = 1;
+
+/// One job's progress as of the last time it was persisted.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JobRecord {
+    pub source_path: String,
+    pub cache_path: PathBuf,
+    pub total_size: i64,
+    pub cached_size: i64,
+    pub complete: bool,
+    /// Fixed-size range indices the parallel download path has already
+    /// written durably, so a resumed job can skip re-fetching them. Always
+    /// empty for jobs that went through the sequential CDC path.
+    #[serde(default)]
+    pub completed_chunks: HashSet<usize>,
+    /// The `--chunk` size `completed_chunks` was computed against. A restart
+    /// with a different chunk size can't reuse these indices — they'd land
+    /// at the wrong byte offsets — so callers must discard `completed_chunks`
+    /// whenever this doesn't match the current run's chunk size. Defaults to
+    /// `0` (never matches a real chunk size) for records written before this
+    /// field existed, so old resume state is discarded rather than misread.
+    #[serde(default)]
+    pub chunk_size: usize,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct JobIndex {
+    jobs: Vec<JobRecord>,
+}
+
+impl JobIndex {
+    /// Loads the index, discarding it (rather than erroring) if it's
+    /// missing, truncated, or from an incompatible version.
+    pub fn load(path: &Path) -> Self {
+        Self::try_load(path).unwrap_or_default()
+    }
+
+    fn try_load(path: &Path) -> io::Result<Self> {
+        let bytes = std::fs::read(path)?;
+        if bytes.len() < 4 {
+            return Ok(Self::default());
+        }
+
+        let version = u32::from_le_bytes(bytes[..4].try_into().unwrap());
+        if version != JOB_INDEX_VERSION {
+            return Ok(Self::default());
+        }
+
+        let decompressed = zstd::stream::decode_all(&bytes[4..])?;
+        serde_json::from_slice(&decompressed)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+
+        let json = serde_json::to_vec(self).map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
+        let compressed = zstd::stream::encode_all(json.as_slice(), 3)?;
+
+        let mut bytes = Vec::with_capacity(4 + compressed.len());
+        bytes.extend_from_slice(&JOB_INDEX_VERSION.to_le_bytes());
+        bytes.extend_from_slice(&compressed);
+
+        let tmp = path.with_extension("tmp");
+        std::fs::write(&tmp, &bytes)?;
+        std::fs::rename(&tmp, path)
+    }
+
+    pub fn get(&self, source_path: &str) -> Option<&JobRecord> {
+        self.jobs.iter().find(|job| job.source_path == source_path)
+    }
+
+    pub fn upsert(&mut self, record: JobRecord) {
+        match self
+            .jobs
+            .iter_mut()
+            .find(|job| job.source_path == record.source_path)
+        {
+            Some(existing) => *existing = record,
+            None => self.jobs.push(record),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_path(label: &str) -> PathBuf {
+        static COUNTER: std::sync::atomic::AtomicU32 = std::sync::atomic::AtomicU32::new(0);
+        let n = COUNTER.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+        std::env::temp_dir().join(format!(
+            "rclone-precache-test-{}-{}-{}.state",
+            label,
+            std::process::id(),
+            n
+        ))
+    }
+
+    fn record(source_path: &str, cached_size: i64, complete: bool) -> JobRecord {
+        JobRecord {
+            source_path: source_path.to_string(),
+            cache_path: PathBuf::from(source_path),
+            total_size: 100,
+            cached_size,
+            complete,
+            completed_chunks: HashSet::new(),
+            chunk_size: 0,
+        }
+    }
+
+    #[test]
+    fn upsert_replaces_the_record_for_the_same_source_path() {
+        let mut index = JobIndex::default();
+        index.upsert(record("a.bin", 50, false));
+        index.upsert(record("a.bin", 100, true));
+
+        assert_eq!(index.jobs.len(), 1);
+        let job = index.get("a.bin").unwrap();
+        assert_eq!(job.cached_size, 100);
+        assert!(job.complete);
+    }
+
+    #[test]
+    fn save_then_load_round_trips() {
+        let path = temp_path("roundtrip");
+        let mut index = JobIndex::default();
+        index.upsert(record("b.bin", 100, true));
+        index.save(&path).unwrap();
+
+        let loaded = JobIndex::load(&path);
+        assert_eq!(loaded.get("b.bin").unwrap().cached_size, 100);
+
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn load_discards_an_incompatible_version_instead_of_misreading_it() {
+        let path = temp_path("bad-version");
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(&(JOB_INDEX_VERSION + 1).to_le_bytes());
+        bytes.extend_from_slice(b"not a valid payload for this version");
+        std::fs::write(&path, &bytes).unwrap();
+
+        let loaded = JobIndex::load(&path);
+        assert!(loaded.jobs.is_empty());
+
+        let _ = std::fs::remove_file(&path);
+    }
+}
@@ -3,7 +3,7 @@ use async_trait::async_trait;
 use std::io;
 use std::path::PathBuf;
 use tokio::fs::File;
-use tokio::io::AsyncRead;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncSeekExt};
 
 #[derive(Clone)]
 pub struct LocalFileSystem {
@@ -59,6 +59,7 @@ impl StorageBackend for LocalFileSystem {
                     .duration_since(std::time::UNIX_EPOCH)
                     .map_err(|e| io::Error::new(io::ErrorKind::Other, e))?
                     .as_secs_f64(),
+                etag: None,
             });
         }
 
@@ -91,6 +92,7 @@ impl StorageBackend for LocalFileSystem {
                 .duration_since(std::time::UNIX_EPOCH)
                 .map_err(|e| io::Error::new(io::ErrorKind::Other, e))?
                 .as_secs_f64(),
+            etag: None,
         })
     }
 
@@ -100,6 +102,25 @@ impl StorageBackend for LocalFileSystem {
         Ok(Box::new(file))
     }
 
+    async fn open_file_range(
+        &self,
+        path: &str,
+        start: u64,
+        end: Option<u64>,
+    ) -> io::Result<Box<dyn AsyncRead + Unpin + Send>> {
+        let full_path = self.resolve_path(path);
+        let mut file = File::open(full_path).await?;
+        file.seek(io::SeekFrom::Start(start)).await?;
+
+        match end {
+            Some(end) => {
+                let len = end.saturating_sub(start) + 1;
+                Ok(Box::new(file.take(len)))
+            }
+            None => Ok(Box::new(file)),
+        }
+    }
+
     async fn file_size(&self, path: &str) -> io::Result<i64> {
         let full_path = self.resolve_path(path);
         let metadata = tokio::fs::metadata(full_path).await?;
@@ -2,19 +2,27 @@ use actix_web::{web, App, HttpResponse, HttpServer, Responder};
 use clap::Parser;
 use std::path::PathBuf;
 
+mod cache_evictor;
 mod cache_manager;
+mod content_store;
 mod directory_sizer;
+mod fuse_fs;
 mod handlers;
+mod job_index;
 mod models;
 mod server;
 mod storage_backend;
 mod local_backend;
+mod s3_backend;
 mod webdav_backend;
+#[cfg(test)]
+mod test_support;
 
-use handlers::{handle_browse, handle_cache_progress, handle_precache};
+use handlers::{handle_browse, handle_cache_progress, handle_precache, handle_stream};
 use server::Server;
 use storage_backend::StorageBackend;
 use local_backend::LocalFileSystem;
+use s3_backend::S3Backend;
 use webdav_backend::WebDAVBackend;
 
 // Include the HTML file at compile time
@@ -47,6 +55,34 @@ struct Args {
     #[arg(long)]
     webdav_password: Option<String>,
 
+    /// Custom CA bundle (PEM) to trust for the WebDAV client
+    #[arg(long)]
+    webdav_ca_cert: Option<PathBuf>,
+
+    /// Skip TLS certificate verification for the WebDAV client (self-signed dev servers)
+    #[arg(long)]
+    webdav_insecure: bool,
+
+    /// S3-compatible endpoint URL (for s3), e.g. a MinIO or Backblaze B2 endpoint
+    #[arg(long)]
+    s3_endpoint: Option<String>,
+
+    /// S3 bucket name (for s3)
+    #[arg(long)]
+    s3_bucket: Option<String>,
+
+    /// S3 region (for s3)
+    #[arg(long, default_value = "us-east-1")]
+    s3_region: String,
+
+    /// S3 access key ID
+    #[arg(long)]
+    s3_access_key: Option<String>,
+
+    /// S3 secret access key
+    #[arg(long)]
+    s3_secret_key: Option<String>,
+
     /// Cache directory path
     #[arg(long)]
     cache: PathBuf,
@@ -58,6 +94,55 @@ struct Args {
     /// Number of cache threads
     #[arg(long, default_value = "2")]
     threads: usize,
+
+    /// Maximum on-disk cache size in GB; least-recently-used entries are
+    /// evicted once this is exceeded. Unset means unbounded.
+    #[arg(long)]
+    cache_budget_gb: Option<f64>,
+
+    /// TLS certificate (PEM) to serve the API and UI over HTTPS
+    #[arg(long)]
+    tls_cert: Option<PathBuf>,
+
+    /// TLS private key (PEM) to serve the API and UI over HTTPS
+    #[arg(long)]
+    tls_key: Option<PathBuf>,
+
+    /// Mount the browsed storage as a read-through FUSE filesystem at this directory
+    #[arg(long)]
+    fuse_mount: Option<PathBuf>,
+}
+
+/// Builds a rustls server config from a PEM certificate chain and private key,
+/// as required by `HttpServer::bind_rustls`.
+fn load_rustls_config(
+    cert_path: &std::path::Path,
+    key_path: &std::path::Path,
+) -> std::io::Result<rustls::ServerConfig> {
+    use std::io::BufReader;
+
+    let cert_chain = rustls_pemfile::certs(&mut BufReader::new(std::fs::File::open(cert_path)?))
+        .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?
+        .into_iter()
+        .map(rustls::Certificate)
+        .collect();
+
+    let mut keys: Vec<rustls::PrivateKey> =
+        rustls_pemfile::pkcs8_private_keys(&mut BufReader::new(std::fs::File::open(key_path)?))
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?
+            .into_iter()
+            .map(rustls::PrivateKey)
+            .collect();
+
+    let key = keys.pop().ok_or_else(|| {
+        std::io::Error::new(std::io::ErrorKind::InvalidData, "no private key found in --tls-key")
+    })?;
+
+    rustls::ServerConfig::builder()
+        .with_safe_defaults()
+        .with_no_client_auth()
+        .with_single_cert(cert_chain, key)
+        .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))
 }
 
 // Handler for serving the index.html
@@ -107,20 +192,58 @@ async fn main() -> std::io::Result<()> {
                 webdav_url.clone(),
                 args.webdav_username,
                 args.webdav_password,
+                args.webdav_ca_cert,
+                args.webdav_insecure,
+            )?)
+        }
+        "s3" => {
+            let s3_bucket = args.s3_bucket.clone().ok_or_else(|| {
+                std::io::Error::new(
+                    std::io::ErrorKind::InvalidInput,
+                    "--s3-bucket is required for s3 mount type",
+                )
+            })?;
+
+            Box::new(S3Backend::new(
+                args.s3_endpoint,
+                s3_bucket,
+                args.s3_region,
+                args.s3_access_key,
+                args.s3_secret_key,
             )?)
         }
         _ => {
-            tracing::error!("Invalid mount type. Use 'local' or 'webdav'");
+            tracing::error!("Invalid mount type. Use 'local', 'webdav', or 's3'");
             std::process::exit(1);
         }
     };
 
+    let cache_budget_bytes = args
+        .cache_budget_gb
+        .map(|gb| (gb * 1024.0 * 1024.0 * 1024.0) as i64);
+
     let server = Server::new(
         storage_backend,
         args.cache,
         args.chunk * 1024 * 1024,
         args.threads,
-    );
+        cache_budget_bytes,
+    )?;
+
+    if let Some(fuse_mount) = args.fuse_mount {
+        let fs = fuse_fs::PrecacheFuse::new(
+            server.storage_backend(),
+            server.cache_manager(),
+            tokio::runtime::Handle::current(),
+        );
+        println!("FUSE mount active at {:?}", fuse_mount);
+        std::thread::spawn(move || {
+            if let Err(e) = fs.mount(&fuse_mount) {
+                tracing::error!("FUSE mount failed: {}", e);
+            }
+        });
+    }
+
     let server_data = web::Data::new(server);
 
     println!("Starting server at http://127.0.0.1:8000");
@@ -129,7 +252,16 @@ async fn main() -> std::io::Result<()> {
         args.threads, args.chunk
     );
 
-    HttpServer::new(move || {
+    let tls_config = match (&args.tls_cert, &args.tls_key) {
+        (Some(cert), Some(key)) => Some(load_rustls_config(cert, key)?),
+        (None, None) => None,
+        _ => {
+            tracing::error!("--tls-cert and --tls-key must be provided together");
+            std::process::exit(1);
+        }
+    };
+
+    let http_server = HttpServer::new(move || {
         let cors = actix_cors::Cors::default()
             .allow_any_origin()
             .allow_any_method()
@@ -145,7 +277,8 @@ async fn main() -> std::io::Result<()> {
                     .route(
                         "/cache-progress/{path:.*}",
                         web::get().to(handle_cache_progress),
-                    ),
+                    )
+                    .route("/stream/{path:.*}", web::get().to(handle_stream)),
             )
             // serve js
             .route(
@@ -182,8 +315,16 @@ async fn main() -> std::io::Result<()> {
             )
             // Serve index.html for all other routes
             .default_service(web::get().to(serve_index))
-    })
-    .bind("0.0.0.0:8000")?
-    .run()
-    .await
+    });
+
+    match tls_config {
+        Some(tls_config) => {
+            println!("TLS enabled");
+            http_server
+                .bind_rustls(("0.0.0.0", 8000), tls_config)?
+                .run()
+                .await
+        }
+        None => http_server.bind("0.0.0.0:8000")?.run().await,
+    }
 }
@@ -58,4 +58,8 @@ pub struct GlobalProgress {
     pub overall_percent: f64,
     pub active_jobs: i32,
     pub cached_size: i64,
+    /// Current on-disk size of the whole cache, as tracked by `CacheEvictor`.
+    pub cache_total: i64,
+    /// Configured eviction budget; `0` means eviction is disabled.
+    pub cache_budget: i64,
 }
\ No newline at end of file
@@ -0,0 +1,217 @@
+//! `StorageBackend` over an S3-compatible object store.
+//!
+//! `list_dir` follows a prefix listing's continuation tokens page by page
+//! and collects every page into the returned `Vec`. File sizes come
+//! straight from that listing's `ObjectMeta`-equivalent fields
+//! (`size`/`e_tag`, no extra per-file HEAD), and those are what
+//! `CacheManager::calculate_directory_size` sums for a directory.
+//! `metadata`/`file_size` map to a HEAD, and `open_file`/`open_file_range`
+//! return a streaming body.
+
+use crate::storage_backend::{ConditionalFetch, FileEntry, StorageBackend};
+use async_trait::async_trait;
+use aws_sdk_s3::config::{Credentials, Region};
+use aws_sdk_s3::Client;
+use std::io;
+use tokio::io::AsyncRead;
+
+#[derive(Clone)]
+pub struct S3Backend {
+    client: Client,
+    bucket: String,
+}
+
+impl S3Backend {
+    pub fn new(
+        endpoint: Option<String>,
+        bucket: String,
+        region: String,
+        access_key: Option<String>,
+        secret_key: Option<String>,
+    ) -> io::Result<Self> {
+        let mut config_builder = aws_sdk_s3::config::Builder::new()
+            .region(Region::new(region))
+            // MinIO and Backblaze B2 both expect path-style addressing rather
+            // than AWS's default virtual-hosted-style bucket subdomains.
+            .force_path_style(true);
+
+        if let Some(endpoint) = endpoint {
+            config_builder = config_builder.endpoint_url(endpoint);
+        }
+
+        if let (Some(access_key), Some(secret_key)) = (access_key, secret_key) {
+            config_builder = config_builder.credentials_provider(Credentials::new(
+                access_key,
+                secret_key,
+                None,
+                None,
+                "rclone-precache",
+            ));
+        }
+
+        Ok(Self {
+            client: Client::from_conf(config_builder.build()),
+            bucket,
+        })
+    }
+
+    fn normalize_prefix(path: &str) -> String {
+        let path = path.trim_start_matches('/');
+        if path.is_empty() {
+            String::new()
+        } else {
+            format!("{}/", path.trim_end_matches('/'))
+        }
+    }
+}
+
+#[async_trait]
+impl StorageBackend for S3Backend {
+    async fn list_dir(&self, path: &str) -> io::Result<Vec<FileEntry>> {
+        let prefix = Self::normalize_prefix(path);
+        let mut entries = Vec::new();
+        let mut continuation_token: Option<String> = None;
+
+        loop {
+            let mut request = self
+                .client
+                .list_objects_v2()
+                .bucket(&self.bucket)
+                .delimiter("/")
+                .prefix(&prefix);
+
+            if let Some(token) = &continuation_token {
+                request = request.continuation_token(token);
+            }
+
+            let response = request
+                .send()
+                .await
+                .map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
+
+            for common_prefix in response.common_prefixes() {
+                if let Some(key_prefix) = common_prefix.prefix() {
+                    let trimmed = key_prefix.trim_end_matches('/');
+                    let name = trimmed.rsplit('/').next().unwrap_or(trimmed).to_string();
+
+                    entries.push(FileEntry {
+                        name,
+                        path: format!("/{}", trimmed),
+                        is_dir: true,
+                        size: None,
+                        modified_time: 0.0,
+                        etag: None,
+                    });
+                }
+            }
+
+            for object in response.contents() {
+                let Some(key) = object.key() else { continue };
+                if key == prefix {
+                    continue;
+                }
+
+                let name = key.rsplit('/').next().unwrap_or(key).to_string();
+                entries.push(FileEntry {
+                    name,
+                    path: format!("/{}", key),
+                    is_dir: false,
+                    size: object.size(),
+                    modified_time: object
+                        .last_modified()
+                        .map(|t| t.secs() as f64)
+                        .unwrap_or(0.0),
+                    etag: object.e_tag().map(|s| s.to_string()),
+                });
+            }
+
+            continuation_token = response.next_continuation_token().map(|s| s.to_string());
+            if continuation_token.is_none() {
+                break;
+            }
+        }
+
+        Ok(entries)
+    }
+
+    async fn metadata(&self, path: &str) -> io::Result<FileEntry> {
+        let key = path.trim_start_matches('/');
+        let response = self
+            .client
+            .head_object()
+            .bucket(&self.bucket)
+            .key(key)
+            .send()
+            .await
+            .map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
+
+        let name = key.rsplit('/').next().unwrap_or(key).to_string();
+        Ok(FileEntry {
+            name,
+            path: path.to_string(),
+            is_dir: false,
+            size: response.content_length(),
+            modified_time: response
+                .last_modified()
+                .map(|t| t.secs() as f64)
+                .unwrap_or(0.0),
+            etag: response.e_tag().map(|s| s.to_string()),
+        })
+    }
+
+    async fn open_file(&self, path: &str) -> io::Result<Box<dyn AsyncRead + Unpin + Send>> {
+        self.open_file_range(path, 0, None).await
+    }
+
+    /// HEADs the object and compares its current ETag to the one the caller
+    /// last cached, rather than relying on S3 honoring `If-None-Match` on
+    /// `GetObject` (support for that varies across S3-compatible backends).
+    async fn open_file_conditional(
+        &self,
+        path: &str,
+        etag: Option<&str>,
+        _last_modified: Option<f64>,
+    ) -> io::Result<ConditionalFetch> {
+        if let Some(etag) = etag {
+            let entry = self.metadata(path).await?;
+            if entry.etag.as_deref() == Some(etag) {
+                return Ok(ConditionalFetch::NotModified);
+            }
+        }
+
+        Ok(ConditionalFetch::Modified(self.open_file(path).await?))
+    }
+
+    async fn open_file_range(
+        &self,
+        path: &str,
+        start: u64,
+        end: Option<u64>,
+    ) -> io::Result<Box<dyn AsyncRead + Unpin + Send>> {
+        let key = path.trim_start_matches('/');
+        let range = match end {
+            Some(end) => Some(format!("bytes={}-{}", start, end)),
+            None if start > 0 => Some(format!("bytes={}-", start)),
+            None => None,
+        };
+
+        let mut request = self.client.get_object().bucket(&self.bucket).key(key);
+        if let Some(range) = range {
+            request = request.range(range);
+        }
+
+        let response = request
+            .send()
+            .await
+            .map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
+
+        Ok(Box::new(response.body.into_async_read()))
+    }
+
+    async fn file_size(&self, path: &str) -> io::Result<i64> {
+        let entry = self.metadata(path).await?;
+        entry
+            .size
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "Not a file"))
+    }
+}
@@ -20,15 +20,21 @@ impl Server {
         cache_path: PathBuf,
         chunk_size: usize,
         cache_threads: usize,
-    ) -> Self {
+        cache_budget_bytes: Option<i64>,
+    ) -> std::io::Result<Self> {
         let storage_arc = Arc::from(storage_backend);
-        Self {
-            cache_manager: Arc::new(CacheManager::new(chunk_size, Arc::clone(&storage_arc))),
+        Ok(Self {
+            cache_manager: Arc::new(CacheManager::new(
+                chunk_size,
+                cache_path.clone(),
+                Arc::clone(&storage_arc),
+                cache_budget_bytes,
+            )?),
             directory_sizer: Arc::new(DirectorySizer::new()),
             storage_backend: storage_arc,
             cache_path,
             cache_threads,
-        }
+        })
     }
 
     pub async fn browse(&self, path: &str) -> Result<Vec<FileInfo>, std::io::Error> {
@@ -89,20 +95,43 @@ impl Server {
         Ok(())
     }
 
+    pub fn storage_backend(&self) -> Arc<dyn StorageBackend> {
+        Arc::clone(&self.storage_backend)
+    }
+
+    pub fn cache_manager(&self) -> Arc<CacheManager> {
+        Arc::clone(&self.cache_manager)
+    }
+
+    /// Streams `path`'s logical bytes, reading through a still-in-progress
+    /// precache job if one is running, or straight from the finished cache
+    /// otherwise. Returns `None` if `path` hasn't been cached or started.
+    pub async fn stream_cached(
+        &self,
+        path: &str,
+    ) -> Option<impl futures::Stream<Item = std::io::Result<bytes::Bytes>>> {
+        self.cache_manager.stream_cached(path).await
+    }
+
     pub async fn get_cache_progress(&self, path: &str) -> Result<GlobalProgress, std::io::Error> {
         if path == "/" || path == "" {
             return Ok(self.cache_manager.get_global_progress().await);
         }
 
         match self.cache_manager.get_progress(path).await {
-            Some(progress) => Ok(GlobalProgress {
-                total_speed: progress.read().current_speed,
-                overall_percent: (progress.read().total_bytes_read as f64
-                    / progress.read().total_size as f64)
-                    * 100.0,
-                active_jobs: 1,
-                cached_size: progress.read().cached_size,
-            }),
+            Some(progress) => {
+                let (cache_total, cache_budget) = self.cache_manager.cache_usage();
+                Ok(GlobalProgress {
+                    total_speed: progress.read().current_speed,
+                    overall_percent: (progress.read().total_bytes_read as f64
+                        / progress.read().total_size as f64)
+                        * 100.0,
+                    active_jobs: 1,
+                    cached_size: progress.read().cached_size,
+                    cache_total,
+                    cache_budget,
+                })
+            }
             None => Err(std::io::Error::new(
                 std::io::ErrorKind::NotFound,
                 "No active cache operation found",
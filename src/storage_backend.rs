@@ -10,6 +10,17 @@ pub struct FileEntry {
     pub is_dir: bool,
     pub size: Option<i64>,
     pub modified_time: f64,
+    /// Strong validator for conditional requests (e.g. WebDAV `getetag`), if
+    /// the backend exposes one.
+    pub etag: Option<String>,
+}
+
+/// Result of a conditional fetch against a previously cached etag/last-modified.
+pub enum ConditionalFetch {
+    /// The remote object hasn't changed since it was last cached.
+    NotModified,
+    /// The remote object is new or changed; here's its current content.
+    Modified(Box<dyn AsyncRead + Unpin + Send>),
 }
 
 #[async_trait]
@@ -22,7 +33,30 @@ pub trait StorageBackend: Send + Sync {
     
     /// Open a file for reading, returns a stream
     async fn open_file(&self, path: &str) -> io::Result<Box<dyn AsyncRead + Unpin + Send>>;
-    
+
+    /// Open a byte range of a file for reading, returns a stream starting at
+    /// `start` and ending at `end` (inclusive), or running to EOF if `end` is `None`.
+    async fn open_file_range(
+        &self,
+        path: &str,
+        start: u64,
+        end: Option<u64>,
+    ) -> io::Result<Box<dyn AsyncRead + Unpin + Send>>;
+
     /// Get the size of a file
     async fn file_size(&self, path: &str) -> io::Result<i64>;
+
+    /// Performs a conditional fetch against a previously recorded etag and/or
+    /// last-modified time, so an unchanged remote object can be skipped
+    /// without a full transfer. Backends that don't support conditional
+    /// requests always report the object as modified.
+    async fn open_file_conditional(
+        &self,
+        path: &str,
+        etag: Option<&str>,
+        last_modified: Option<f64>,
+    ) -> io::Result<ConditionalFetch> {
+        let _ = (etag, last_modified);
+        Ok(ConditionalFetch::Modified(self.open_file(path).await?))
+    }
 }
@@ -0,0 +1,20 @@
+//! Shared fixtures for unit tests across modules, so each test file doesn't
+//! redefine the same scratch-directory helper.
+
+use std::path::PathBuf;
+
+/// A fresh, empty directory under the system temp dir, namespaced by
+/// `label`, the current process id, and a monotonic counter so concurrent
+/// test runs never collide. Callers are responsible for cleaning it up.
+pub fn temp_cache_root(label: &str) -> PathBuf {
+    static COUNTER: std::sync::atomic::AtomicU32 = std::sync::atomic::AtomicU32::new(0);
+    let n = COUNTER.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+    let root = std::env::temp_dir().join(format!(
+        "rclone-precache-test-{}-{}-{}",
+        label,
+        std::process::id(),
+        n
+    ));
+    std::fs::create_dir_all(&root).unwrap();
+    root
+}